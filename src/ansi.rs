@@ -0,0 +1,158 @@
+use crate::vga_buffer::{Color, ColorCode};
+
+pub const ESC: u8 = 0x1B;
+const MAX_PARAMS: usize = 8;
+
+const DEFAULT_FOREGROUND: u8 = Color::White as u8;
+const DEFAULT_BACKGROUND: u8 = Color::Black as u8;
+
+// ANSI color numbers are black, red, green, yellow, blue, magenta, cyan, white.
+const ANSI_TO_VGA: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+#[derive(Clone, Copy)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    pub private: bool,
+    pub command: u8,
+}
+
+impl Csi {
+    const EMPTY: Csi = Csi { params: [0; MAX_PARAMS], count: 0, private: false, command: 0 };
+
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count]
+    }
+
+    /// The n-th parameter, with missing or zero values replaced by `default`.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value > 0 => value,
+            _ => default,
+        }
+    }
+}
+
+pub enum Action {
+    None,
+    Print(u8),
+    Escape(u8),
+    Csi(Csi),
+}
+
+pub struct Parser {
+    state: State,
+    csi: Csi,
+    bold: bool,
+}
+
+impl Parser {
+    pub const fn new() -> Parser {
+        Parser { state: State::Ground, csi: Csi::EMPTY, bold: false }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Action {
+        match self.state {
+            State::Ground => {
+                if byte == ESC {
+                    self.state = State::Escape;
+                    Action::None
+                } else {
+                    Action::Print(byte)
+                }
+            }
+            State::Escape => {
+                if byte == b'[' {
+                    self.state = State::Csi;
+                    self.csi = Csi::EMPTY;
+                    Action::None
+                } else {
+                    self.state = State::Ground;
+                    Action::Escape(byte)
+                }
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.csi.count == 0 {
+                        self.csi.count = 1;
+                    }
+                    let param = &mut self.csi.params[self.csi.count - 1];
+                    *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    Action::None
+                }
+                b';' => {
+                    if self.csi.count == 0 {
+                        self.csi.count = 1;
+                    }
+                    if self.csi.count < MAX_PARAMS {
+                        self.csi.count += 1;
+                    }
+                    Action::None
+                }
+                b'?' => {
+                    self.csi.private = true;
+                    Action::None
+                }
+                0x20..=0x3F => Action::None,
+                0x40..=0x7E => {
+                    self.state = State::Ground;
+                    self.csi.command = byte;
+                    Action::Csi(self.csi)
+                }
+                ESC => {
+                    self.state = State::Escape;
+                    Action::None
+                }
+                _ => {
+                    self.state = State::Ground;
+                    Action::None
+                }
+            },
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = State::Ground;
+        self.bold = false;
+    }
+
+    /// Applies an SGR (`ESC[...m`) sequence to `color` and returns the new color.
+    pub fn apply_sgr(&mut self, csi: &Csi, color: ColorCode) -> ColorCode {
+        let mut foreground = color.attribute() & 0x0F;
+        let mut background = color.attribute() >> 4;
+        let params = if csi.params().is_empty() { &[0][..] } else { csi.params() };
+
+        for &param in params {
+            match param {
+                0 => {
+                    self.bold = false;
+                    foreground = DEFAULT_FOREGROUND;
+                    background = DEFAULT_BACKGROUND;
+                }
+                1 => {
+                    self.bold = true;
+                    foreground |= 0x08;
+                }
+                22 => {
+                    self.bold = false;
+                    foreground &= 0x07;
+                }
+                30..=37 => foreground = ANSI_TO_VGA[(param - 30) as usize] | if self.bold { 0x08 } else { 0 },
+                39 => foreground = DEFAULT_FOREGROUND,
+                40..=47 => background = ANSI_TO_VGA[(param - 40) as usize],
+                49 => background = DEFAULT_BACKGROUND,
+                90..=97 => foreground = ANSI_TO_VGA[(param - 90) as usize] | 0x08,
+                100..=107 => background = ANSI_TO_VGA[(param - 100) as usize] | 0x08,
+                _ => {}
+            }
+        }
+        ColorCode::from_attribute(background << 4 | foreground)
+    }
+}
@@ -0,0 +1,238 @@
+use crate::io::Sink;
+
+pub const MAX_ARGS: usize = 32;
+
+pub fn is_operator_byte(b: u8) -> bool {
+    b == b'|' || b == b'<' || b == b'>'
+}
+
+/// Collects one argument's bytes in the tokenizer's storage.
+struct Word<'s> {
+    storage: &'s mut [u8],
+    len: usize,
+    overflowed: bool,
+}
+
+impl Sink for Word<'_> {
+    fn write_byte(&mut self, byte: u8) {
+        if self.len >= self.storage.len() {
+            self.overflowed = true;
+            return;
+        }
+        self.storage[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn column(&self) -> usize {
+        0
+    }
+}
+
+/// The name after a `$`, and how many bytes of `rest` (the text after the `$`) it takes up.
+fn variable_name(rest: &str) -> Result<Option<(&str, usize)>, &'static str> {
+    if rest.starts_with('?') {
+        return Ok(Some(("?", 1)));
+    }
+    if let Some(braced) = rest.strip_prefix('{') {
+        let end = braced.find('}').ok_or("Missing '}' in variable reference")?;
+        return Ok(Some((&braced[..end], end + 2)));
+    }
+    let end = rest
+        .bytes()
+        .position(|c| !(c.is_ascii_alphanumeric() || c == b'_'))
+        .unwrap_or(rest.len());
+    Ok((end > 0).then(|| (&rest[..end], end)))
+}
+
+/// Splits a command line into arguments, removing quotes and escapes. `$NAME`, `${NAME}` and
+/// `$?` outside single quotes are replaced by what `variable` writes for them; the value always
+/// stays inside its argument, so spaces and `|`, `<` or `>` in it are plain characters.
+pub fn tokenize<'a>(
+    input: &str,
+    storage: &'a mut [u8],
+    argv: &mut [&'a str],
+    operators: &mut [bool],
+    variable: fn(&str, &mut dyn Sink),
+) -> Result<usize, &'static str> {
+    let bytes = input.as_bytes();
+    let mut ranges = [(0usize, 0usize); MAX_ARGS];
+    let mut count = 0;
+    let mut word = Word { storage, len: 0, overflowed: false };
+    let mut i = 0;
+
+    while i < bytes.len() {
+        while i < bytes.len() && (bytes[i] == b' ' || bytes[i] == b'\t') {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+        if count >= MAX_ARGS.min(argv.len()) {
+            return Err("Too many arguments");
+        }
+
+        let start = word.len;
+        if is_operator_byte(bytes[i]) {
+            let op_len = if bytes[i] == b'>' && bytes.get(i + 1) == Some(&b'>') { 2 } else { 1 };
+            for &b in &bytes[i..i + op_len] {
+                word.write_byte(b);
+            }
+            if word.overflowed {
+                return Err("Command line too long");
+            }
+            i += op_len;
+            ranges[count] = (start, word.len);
+            if let Some(slot) = operators.get_mut(count) {
+                *slot = true;
+            }
+            count += 1;
+            continue;
+        }
+
+        let mut quote = None;
+        // An argument made only of variables that expand to nothing is dropped.
+        let mut literal = false;
+        while i < bytes.len() {
+            let b = bytes[i];
+            let out = match (quote, b) {
+                (None, b' ') | (None, b'\t') => break,
+                (None, _) if is_operator_byte(b) => break,
+                (None, b'\'') | (None, b'"') => {
+                    quote = Some(b);
+                    literal = true;
+                    i += 1;
+                    continue;
+                }
+                (Some(q), _) if b == q => {
+                    quote = None;
+                    i += 1;
+                    continue;
+                }
+                (None, b'\\') if i + 1 < bytes.len() => {
+                    i += 1;
+                    bytes[i]
+                }
+                (Some(b'"'), b'\\') if i + 1 < bytes.len() && matches!(bytes[i + 1], b'"' | b'\\' | b'$') => {
+                    i += 1;
+                    bytes[i]
+                }
+                (None, b'$') | (Some(b'"'), b'$') => match variable_name(&input[i + 1..])? {
+                    Some((name, consumed)) => {
+                        variable(name, &mut word);
+                        i += 1 + consumed;
+                        continue;
+                    }
+                    None => b,
+                },
+                _ => b,
+            };
+            word.write_byte(out);
+            literal = true;
+            i += 1;
+        }
+        if quote.is_some() {
+            return Err("Unterminated quote");
+        }
+        if word.overflowed {
+            return Err("Command line too long");
+        }
+        if word.len == start && !literal {
+            continue;
+        }
+
+        ranges[count] = (start, word.len);
+        if let Some(slot) = operators.get_mut(count) {
+            *slot = false;
+        }
+        count += 1;
+    }
+
+    let storage: &'a [u8] = word.storage;
+    for (slot, &(start, end)) in argv.iter_mut().zip(ranges[..count].iter()) {
+        *slot = core::str::from_utf8(&storage[start..end]).map_err(|_| "Invalid characters in command line")?;
+    }
+    Ok(count)
+}
+
+pub struct Flags<'a> {
+    short: [bool; 128],
+    long: [(&'a str, Option<&'a str>); MAX_ARGS],
+    long_count: usize,
+    positional: [&'a str; MAX_ARGS],
+    positional_count: usize,
+}
+
+impl<'a> Flags<'a> {
+    pub fn parse(args: &[&'a str]) -> Self {
+        let mut flags = Flags {
+            short: [false; 128],
+            long: [("", None); MAX_ARGS],
+            long_count: 0,
+            positional: [""; MAX_ARGS],
+            positional_count: 0,
+        };
+        let mut only_positional = false;
+
+        for &arg in args.iter().take(MAX_ARGS) {
+            if only_positional || arg == "-" || !arg.starts_with('-') || is_number(arg) {
+                flags.positional[flags.positional_count] = arg;
+                flags.positional_count += 1;
+            } else if arg == "--" {
+                only_positional = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                flags.long[flags.long_count] = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                flags.long_count += 1;
+            } else {
+                for c in arg[1..].bytes() {
+                    if c < 128 {
+                        flags.short[c as usize] = true;
+                    }
+                }
+            }
+        }
+        flags
+    }
+
+    pub fn has(&self, short: char) -> bool {
+        (short as usize) < 128 && self.short[short as usize]
+    }
+
+    pub fn has_long(&self, name: &str) -> bool {
+        self.long[..self.long_count].iter().any(|&(n, _)| n == name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&'a str> {
+        self.long[..self.long_count].iter().rev().find(|&&(n, _)| n == name).and_then(|&(_, v)| v)
+    }
+
+    pub fn positional(&self) -> &[&'a str] {
+        &self.positional[..self.positional_count]
+    }
+
+    pub fn check(&self, allowed_short: &str, allowed_long: &[&str]) -> Result<(), &'static str> {
+        let short_ok = (0..128u8).all(|c| !self.short[c as usize] || allowed_short.as_bytes().contains(&c));
+        let long_ok = self.long[..self.long_count].iter().all(|&(n, _)| allowed_long.contains(&n));
+        if short_ok && long_ok { Ok(()) } else { Err("Unknown option") }
+    }
+}
+
+fn is_number(arg: &str) -> bool {
+    arg.len() > 1 && arg[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+pub fn parse_number(s: &str) -> Option<usize> {
+    if s.is_empty() || s.len() > 9 {
+        return None;
+    }
+    let mut value = 0usize;
+    for c in s.bytes() {
+        if !c.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (c - b'0') as usize;
+    }
+    Some(value)
+}
@@ -1,5 +1,7 @@
 use crate::vga_buffer;
 use crate::file_system::OsFileSystem;
+use crate::io::{Io, Sink};
+use crate::line_editor;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Opcode {
@@ -365,7 +367,7 @@ impl CodeExecutor {
         }
     }
 
-    pub fn execute(&mut self, bytecode_len: usize, fs: Option<&OsFileSystem>, writer: &mut vga_buffer::Writer) -> Result<(), &'static str> {
+    pub fn execute(&mut self, bytecode_len: usize, writer: &mut Io) -> Result<(), &'static str> {
         self.cpu.reset();
         let mut instruction_count = 0;
 
@@ -462,7 +464,7 @@ impl CodeExecutor {
                 }
                 0xFE => { 
 
-                    let value = read_u32_from_input(writer, fs)?;
+                    let value = read_u32_from_input(writer)?;
                     self.cpu.eax = value;
                     self.cpu.eip += 1;
                 }
@@ -474,14 +476,9 @@ impl CodeExecutor {
                     self.cpu.eip = (self.cpu.eip as i32 + 2 + offset as i32) as usize;
                 }
                 0xFF => {
-                    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black);
-                    writer.write_string("PRINT EAX: ");
-                    let mut buf = [0u8; 20];
-                    writer.write_string(&vga_buffer::int_to_string(self.cpu.eax as usize, &mut buf));
-                    writer.write_string(" (decimal) = 0x");
-                    writer.write_string(&vga_buffer::hex_to_string(self.cpu.eax, &mut buf));
-                    writer.write_string(" (hex)\n");
-                    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+                    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black));
+                    writeln!(writer, "PRINT EAX: {} (decimal) = 0x{:X} (hex)", self.cpu.eax as i32, self.cpu.eax);
+                    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
                     self.cpu.eip += 1;
                 }
                 0xE2 => {
@@ -522,55 +519,41 @@ impl CodeExecutor {
             return Err("Program execution limit exceeded");
         }
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black));
         writer.write_string("Program execution completed successfully!\n");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         writer.write_string("\nFinal register values:\n");
         writer.write_string("======================\n");
 
-        let mut buf = [0u8; 20];
-
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black));
         writer.write_string("EAX");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         writer.write_string(" (main result): ");
-        writer.write_string(&vga_buffer::int_to_string(self.cpu.eax as usize, &mut buf));
-        writer.write_string(" (decimal) = 0x");
-        writer.write_string(&vga_buffer::hex_to_string(self.cpu.eax, &mut buf));
-        writer.write_string(" (hex)\n");
+        writeln!(writer, "{} (decimal) = 0x{:X} (hex)", self.cpu.eax as i32, self.cpu.eax);
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black));
         writer.write_string("EBX");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         writer.write_string(" (secondary):   ");
-        writer.write_string(&vga_buffer::int_to_string(self.cpu.ebx as usize, &mut buf));
-        writer.write_string(" (decimal) = 0x");
-        writer.write_string(&vga_buffer::hex_to_string(self.cpu.ebx, &mut buf));
-        writer.write_string(" (hex)\n");
+        writeln!(writer, "{} (decimal) = 0x{:X} (hex)", self.cpu.ebx as i32, self.cpu.ebx);
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black));
         writer.write_string("ECX");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         writer.write_string(" (counter):     ");
-        writer.write_string(&vga_buffer::int_to_string(self.cpu.ecx as usize, &mut buf));
-        writer.write_string(" (decimal) = 0x");
-        writer.write_string(&vga_buffer::hex_to_string(self.cpu.ecx, &mut buf));
-        writer.write_string(" (hex)\n");
+        writeln!(writer, "{} (decimal) = 0x{:X} (hex)", self.cpu.ecx as i32, self.cpu.ecx);
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black));
         writer.write_string("EDX");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         writer.write_string(" (data):        ");
-        writer.write_string(&vga_buffer::int_to_string(self.cpu.edx as usize, &mut buf));
-        writer.write_string(" (decimal) = 0x");
-        writer.write_string(&vga_buffer::hex_to_string(self.cpu.edx, &mut buf));
-        writer.write_string(" (hex)\n");
+        writeln!(writer, "{} (decimal) = 0x{:X} (hex)", self.cpu.edx as i32, self.cpu.edx);
 
         if self.cpu.eax == 15 && self.cpu.ebx == 5 {
             writer.write_string("\n");
-            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black);
+            writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black));
             writer.write_string("Sample program explanation:\n");
-            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+            writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
             writer.write_string("- Loaded 10 into EAX register\n");
             writer.write_string("- Loaded 5 into EBX register\n");
             writer.write_string("- Added EBX (5) to EAX (10)\n");
@@ -583,66 +566,36 @@ impl CodeExecutor {
     }
 }
 
-fn read_u32_from_input(writer: &mut vga_buffer::Writer, fs: Option<&OsFileSystem>) -> Result<u32, &'static str> {
+fn read_u32_from_input(writer: &mut Io) -> Result<u32, &'static str> {
 
-    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black);
+    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black));
     writer.write_string("Input (decimal number): ");
-    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
-
-    let mut line_buf = [0u8; 32];
-    let read_len = match vga_buffer::read_line(writer, &mut line_buf) {
-        Ok(n) => n,
-        Err(_) => {
-
-            if let Some(fs) = fs {
-                if let Ok(data) = fs.read_file("/stdin") {
-
-                    let s = core::str::from_utf8(data).map_err(|_| "Invalid UTF-8 in /stdin")?;
-                    let s = s.trim();
-                    if s.is_empty() {
-                        writer.write_string("\nNo input in /stdin\n");
-                        return Err("No input available");
-                    }
-
-                    return s.parse::<u32>().map_err(|_| "Failed to parse /stdin as u32");
-                } else {
-                    writer.write_string("\nNo /stdin file found\n");
-                    return Err("No input available");
-                }
-            } else {
-                writer.write_string("\nNo input provider available\n");
-                return Err("No input available");
-            }
-        }
-    };
+    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
 
-    if read_len == 0 {
-        writer.write_string("\nNo input typed\n");
-        return Err("No input available");
+    if let Some(stdin) = writer.stdin.as_mut() {
+        let line = stdin.read_line().ok_or("No input available")?;
+        let s = core::str::from_utf8(line).map_err(|_| "Invalid UTF-8 from input")?.trim();
+        writer.write_string(s);
+        writer.write_string("\n");
+        return s.parse::<u32>().map_err(|_| "Failed to parse input as u32");
     }
 
-    let s = core::str::from_utf8(&line_buf[..read_len]).map_err(|_| "Invalid UTF-8 from input")?;
-    let s_trim = s.trim();
-    if s_trim.is_empty() {
-        writer.write_string("\nEmpty input\n");
-        return Err("Empty input");
-    }
-    match s_trim.parse::<u32>() {
-        Ok(v) => {
-            writer.write_string("\n"); 
-            Ok(v)
-        }
-        Err(_) => {
-            writer.write_string("\nInvalid number format\n");
-            Err("Failed to parse input as u32")
-        }
+    let mut line_buf = [0u8; 32];
+    let s = line_editor::read_line(writer.console, &mut line_buf).trim();
+    if s.is_empty() {
+        writer.write_string("No input typed\n");
+        return Err("No input available");
     }
+    s.parse::<u32>().map_err(|_| {
+        writer.write_string("Invalid number format\n");
+        "Failed to parse input as u32"
+    })
 }
 
 pub fn execute_code_file(
     filename: &str,
     fs: &OsFileSystem,
-    writer: &mut vga_buffer::Writer,
+    writer: &mut Io,
 ) -> Result<(), &'static str> {
 
     let file_data = fs.read_file(filename).map_err(|_| "File not found")?;
@@ -657,7 +610,7 @@ pub fn execute_code_file(
 
     writer.write_string("Compiling and executing CODE program...\n");
 
-    executor.execute(bytecode_len, Some(fs), writer)
+    executor.execute(bytecode_len, writer)
 }
 
 pub fn create_sample_program() -> &'static str {
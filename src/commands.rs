@@ -0,0 +1,110 @@
+use crate::io::{Io, Sink};
+use crate::vga_buffer;
+
+pub type CommandHandler = fn(&mut Io, &[&str]) -> i32;
+
+const MAX_COMMANDS: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub handler: CommandHandler,
+}
+
+impl Command {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|&alias| alias == name)
+    }
+}
+
+static mut REGISTRY: [Option<Command>; MAX_COMMANDS] = [None; MAX_COMMANDS];
+static mut COMMAND_COUNT: usize = 0;
+
+fn registry() -> &'static [Option<Command>; MAX_COMMANDS] {
+    unsafe { &*(&raw const REGISTRY) }
+}
+
+pub fn register(command: Command) -> Result<(), &'static str> {
+    let taken = |name: &str| find(name).is_some();
+    if taken(command.name) || command.aliases.iter().any(|&alias| taken(alias)) {
+        return Err("Command name already registered");
+    }
+
+    unsafe {
+        if COMMAND_COUNT >= MAX_COMMANDS {
+            return Err("Command table is full");
+        }
+        REGISTRY[COMMAND_COUNT] = Some(command);
+        COMMAND_COUNT += 1;
+    }
+    Ok(())
+}
+
+pub fn register_all(commands: &[Command]) {
+    for &command in commands {
+        let _ = register(command);
+    }
+}
+
+pub fn count() -> usize {
+    unsafe { COMMAND_COUNT }
+}
+
+pub fn get(index: usize) -> Option<Command> {
+    if index < count() { registry()[index] } else { None }
+}
+
+pub fn find(name: &str) -> Option<Command> {
+    registry()[..count()].iter().flatten().find(|command| command.matches(name)).copied()
+}
+
+pub fn for_each_name<F: FnMut(&'static str)>(mut f: F) {
+    for command in registry()[..count()].iter().flatten() {
+        f(command.name);
+        for &alias in command.aliases {
+            f(alias);
+        }
+    }
+}
+
+pub fn write_usage(writer: &mut dyn Sink, command: &Command) {
+    writer.write_string("Usage: ");
+    writer.write_string(command.usage);
+    writer.write_string("\n");
+}
+
+pub fn write_summary_line(writer: &mut dyn Sink, command: &Command) {
+    const USAGE_WIDTH: usize = 26;
+
+    writer.write_string("  ");
+    writer.write_string(command.usage);
+    for _ in command.usage.len()..USAGE_WIDTH {
+        writer.write_byte(b' ');
+    }
+    writer.write_string(" - ");
+    writer.write_string(command.summary);
+    writer.write_string("\n");
+}
+
+pub fn write_details(writer: &mut dyn Sink, command: &Command) {
+    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black));
+    writer.write_string(command.name);
+    writer.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
+    writer.write_string(" - ");
+    writer.write_string(command.summary);
+    writer.write_string("\n");
+    write_usage(writer, command);
+    if !command.aliases.is_empty() {
+        writer.write_string("Aliases: ");
+        for (i, &alias) in command.aliases.iter().enumerate() {
+            if i > 0 {
+                writer.write_string(", ");
+            }
+            writer.write_string(alias);
+        }
+        writer.write_string("\n");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{commands, cp437, file_system};
+
+pub const MAX_CANDIDATES: usize = 32;
+const NAME_LEN: usize = 64;
+
+/// Completions for the word under the cursor, in the CP437 glyphs the line editor holds.
+pub struct Candidates {
+    names: [[u8; NAME_LEN]; MAX_CANDIDATES],
+    lens: [usize; MAX_CANDIDATES],
+    dirs: [bool; MAX_CANDIDATES],
+    count: usize,
+}
+
+impl Candidates {
+    fn new() -> Self {
+        Self {
+            names: [[0; NAME_LEN]; MAX_CANDIDATES],
+            lens: [0; MAX_CANDIDATES],
+            dirs: [false; MAX_CANDIDATES],
+            count: 0,
+        }
+    }
+
+    /// Adds `prefix` (glyphs) followed by `name` (UTF-8) if the result starts with `word`.
+    fn add(&mut self, word: &[u8], prefix: &[u8], name: &[u8], is_dir: bool) {
+        if self.count >= MAX_CANDIDATES || prefix.len() + name.len() > NAME_LEN {
+            return;
+        }
+        let slot = &mut self.names[self.count];
+        slot[..prefix.len()].copy_from_slice(prefix);
+        let len = prefix.len() + cp437::decode_utf8(name, &mut slot[prefix.len()..]);
+        if !slot[..len].starts_with(word) {
+            return;
+        }
+        if (0..self.count).any(|i| self.names[i][..self.lens[i]] == self.names[self.count][..len]) {
+            return;
+        }
+        self.lens[self.count] = len;
+        self.dirs[self.count] = is_dir;
+        self.count += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn get(&self, index: usize) -> (&[u8], bool) {
+        (&self.names[index][..self.lens[index]], self.dirs[index])
+    }
+
+    pub fn common_prefix_len(&self) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+        let first = &self.names[0][..self.lens[0]];
+        let mut len = first.len();
+        for i in 1..self.count {
+            let other = &self.names[i][..self.lens[i]];
+            len = len.min(other.len());
+            while len > 0 && first[..len] != other[..len] {
+                len -= 1;
+            }
+        }
+        len
+    }
+}
+
+pub fn word_start(line: &[u8], cursor: usize) -> usize {
+    let mut start = cursor;
+    while start > 0 && line[start - 1] != b' ' {
+        start -= 1;
+    }
+    start
+}
+
+pub fn complete(line: &[u8], cursor: usize) -> (usize, Candidates) {
+    let start = word_start(line, cursor);
+    let word = &line[start..cursor];
+    let mut candidates = Candidates::new();
+
+    if line[..start].iter().all(|&b| b == b' ') {
+        commands::for_each_name(|name| candidates.add(word, b"", name.as_bytes(), false));
+        return (start, candidates);
+    }
+
+    let split = word.iter().rposition(|&b| b == b'/').map(|i| i + 1).unwrap_or(0);
+    let dir_part = &word[..split];
+    let mut dir_path = [0u8; 3 * NAME_LEN];
+    let dir_len = cp437::encode_utf8(dir_part, &mut dir_path);
+
+    file_system::with_fs(|fs| {
+        let dir_path = core::str::from_utf8(&dir_path[..dir_len]).unwrap_or("");
+        if let Ok(folders) = fs.list_directory(dir_path) {
+            for folder in folders.iter().flatten() {
+                candidates.add(word, dir_part, folder, true);
+            }
+        }
+        for file in fs.list_all_files().iter().flatten() {
+            candidates.add(word, b"", file, false);
+        }
+    });
+
+    (start, candidates)
+}
@@ -0,0 +1,97 @@
+use crate::vga_buffer::{self, Buffer, Color, ColorCode, Scrollback, Writer, MAX_HEIGHT, MAX_WIDTH};
+use crate::io::Sink;
+use crate::{interrupts, statusbar};
+
+pub const CONSOLE_COUNT: usize = 4;
+
+static mut SCREENS: [Buffer; CONSOLE_COUNT] = unsafe { core::mem::zeroed() };
+static mut SCROLLBACKS: [Scrollback; CONSOLE_COUNT] = unsafe { core::mem::zeroed() };
+static mut WRITERS: [Option<Writer>; CONSOLE_COUNT] = [const { None }; CONSOLE_COUNT];
+static mut ACTIVE: usize = 0;
+
+fn screen(index: usize) -> &'static mut Buffer {
+    unsafe { &mut *(&raw mut SCREENS[index]) }
+}
+
+fn vga() -> &'static mut Buffer {
+    unsafe { &mut *vga_buffer::hardware_buffer() }
+}
+
+fn writers() -> &'static mut [Option<Writer>; CONSOLE_COUNT] {
+    unsafe { &mut *(&raw mut WRITERS) }
+}
+
+pub fn copy_screen(from: &Buffer, to: &mut Buffer) {
+    for row in 0..MAX_HEIGHT {
+        for col in 0..MAX_WIDTH {
+            to.chars[row][col].write(from.chars[row][col].read());
+        }
+    }
+}
+
+pub fn init() {
+    let writers = writers();
+    if writers[0].is_some() {
+        return;
+    }
+    for index in 0..CONSOLE_COUNT {
+        let buffer = if index == 0 { vga() } else { screen(index) };
+        let scrollback = unsafe { &mut *(&raw mut SCROLLBACKS[index]) };
+        let mut writer = Writer::new(buffer, scrollback);
+        if index > 0 {
+            writer.clear_screen();
+            writer.color_code = ColorCode::new(Color::LightCyan, Color::Black);
+            write!(writer, "Anomia OS - console {} (Alt+F1..F4 to switch)\n\n", index + 1);
+            writer.color_code = ColorCode::new(Color::White, Color::Black);
+        }
+        writers[index] = Some(writer);
+    }
+}
+
+pub fn active() -> usize {
+    unsafe { ACTIVE }
+}
+
+pub fn get(index: usize) -> &'static mut Writer {
+    init();
+    writers()[index].as_mut().unwrap()
+}
+
+pub fn writer() -> &'static mut Writer {
+    get(active())
+}
+
+/// Fits every console to the current text mode size and status bar.
+pub fn resize() {
+    interrupts::without_interrupts(|| {
+        for index in 0..CONSOLE_COUNT {
+            get(index).resize();
+        }
+    });
+    statusbar::draw();
+}
+
+pub fn switch(index: usize) -> bool {
+    let current = active();
+    if index >= CONSOLE_COUNT || index == current {
+        return false;
+    }
+    init();
+
+    interrupts::without_interrupts(|| {
+        let old = get(current);
+        old.snap_to_bottom();
+        let offscreen = screen(current);
+        copy_screen(old.buffer, offscreen);
+        let visible = core::mem::replace(&mut old.buffer, offscreen);
+
+        let new = get(index);
+        copy_screen(new.buffer, visible);
+        new.buffer = visible;
+
+        unsafe { ACTIVE = index };
+        new.show_cursor();
+    });
+    statusbar::draw();
+    true
+}
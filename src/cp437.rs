@@ -0,0 +1,122 @@
+pub const UNKNOWN_GLYPH: u8 = 0xFE;
+
+const HIGH_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+// Characters without a CP437 glyph that have a close enough stand-in.
+const FALLBACKS: [(char, u8); 5] = [
+    ('´', b'\''),
+    ('✓', 0xFB),
+    ('✗', b'x'),
+    ('β', 0xE1),
+    ('\u{FFFD}', UNKNOWN_GLYPH),
+];
+
+pub fn from_char(c: char) -> u8 {
+    if c.is_ascii() {
+        return c as u8;
+    }
+    if let Some(index) = HIGH_HALF.iter().position(|&glyph| glyph == c) {
+        return 0x80 + index as u8;
+    }
+    FALLBACKS
+        .iter()
+        .find(|&&(from, _)| from == c)
+        .map_or(UNKNOWN_GLYPH, |&(_, glyph)| glyph)
+}
+
+pub fn to_char(glyph: u8) -> char {
+    if glyph < 0x80 { glyph as char } else { HIGH_HALF[(glyph - 0x80) as usize] }
+}
+
+/// Decodes UTF-8 text into CP437 glyphs, returning how many were written.
+pub fn decode_utf8(text: &[u8], out: &mut [u8]) -> usize {
+    let mut decoder = Utf8Decoder::new();
+    let mut len = 0;
+    for &byte in text {
+        if len >= out.len() {
+            break;
+        }
+        if let Some(glyph) = decoder.feed_glyph(byte) {
+            out[len] = glyph;
+            len += 1;
+        }
+    }
+    len
+}
+
+/// Encodes CP437 glyphs as UTF-8, returning how many bytes were written.
+/// Stops before a character that would not fit completely.
+pub fn encode_utf8(glyphs: &[u8], out: &mut [u8]) -> usize {
+    let mut len = 0;
+    for &glyph in glyphs {
+        let c = to_char(glyph);
+        if len + c.len_utf8() > out.len() {
+            break;
+        }
+        len += c.encode_utf8(&mut out[len..]).len();
+    }
+    len
+}
+
+/// Index where the UTF-8 character ending at `len` starts.
+pub fn previous_char_start(text: &[u8], len: usize) -> usize {
+    let mut index = len.saturating_sub(1);
+    while index > 0 && text[index] & 0xC0 == 0x80 {
+        index -= 1;
+    }
+    index
+}
+
+pub struct Utf8Decoder {
+    codepoint: u32,
+    remaining: u8,
+}
+
+impl Utf8Decoder {
+    pub const fn new() -> Utf8Decoder {
+        Utf8Decoder { codepoint: 0, remaining: 0 }
+    }
+
+    /// Feeds one byte; returns a character once a sequence is complete.
+    /// Malformed input decodes to U+FFFD.
+    pub fn feed(&mut self, byte: u8) -> Option<char> {
+        if byte & 0xC0 == 0x80 {
+            if self.remaining == 0 {
+                return Some('\u{FFFD}');
+            }
+            self.codepoint = self.codepoint << 6 | (byte & 0x3F) as u32;
+            self.remaining -= 1;
+            if self.remaining > 0 {
+                return None;
+            }
+            return Some(char::from_u32(self.codepoint).unwrap_or('\u{FFFD}'));
+        }
+
+        let (bits, remaining) = match byte {
+            0x00..=0x7F => (byte as u32, 0),
+            0xC2..=0xDF => ((byte & 0x1F) as u32, 1),
+            0xE0..=0xEF => ((byte & 0x0F) as u32, 2),
+            0xF0..=0xF4 => ((byte & 0x07) as u32, 3),
+            _ => {
+                self.remaining = 0;
+                return Some('\u{FFFD}');
+            }
+        };
+        self.codepoint = bits;
+        self.remaining = remaining;
+        if remaining == 0 { Some(byte as char) } else { None }
+    }
+
+    pub fn feed_glyph(&mut self, byte: u8) -> Option<u8> {
+        self.feed(byte).map(from_char)
+    }
+}
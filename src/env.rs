@@ -0,0 +1,356 @@
+use crate::commands::{self, Command};
+use crate::console::{self, CONSOLE_COUNT};
+use crate::io::{Io, Sink};
+
+pub const PROFILE_FILE: &str = "~/.profile";
+
+const MAX_VARIABLES: usize = 32;
+const MAX_ALIASES: usize = 16;
+const NAME_LEN: usize = 32;
+const VALUE_LEN: usize = 128;
+
+#[derive(Clone, Copy)]
+struct Variable {
+    name: [u8; NAME_LEN],
+    name_len: usize,
+    value: [u8; VALUE_LEN],
+    value_len: usize,
+    exported: bool,
+    used: bool,
+}
+
+const EMPTY_VARIABLE: Variable = Variable {
+    name: [0; NAME_LEN],
+    name_len: 0,
+    value: [0; VALUE_LEN],
+    value_len: 0,
+    exported: false,
+    used: false,
+};
+
+// Each console has its own variables; aliases are shared.
+static mut VARIABLES: [[Variable; MAX_VARIABLES]; CONSOLE_COUNT] = [[EMPTY_VARIABLE; MAX_VARIABLES]; CONSOLE_COUNT];
+static mut ALIASES: [Variable; MAX_ALIASES] = [EMPTY_VARIABLE; MAX_ALIASES];
+
+fn variables() -> &'static mut [Variable; MAX_VARIABLES] {
+    unsafe { &mut (*(&raw mut VARIABLES))[console::active()] }
+}
+
+fn aliases() -> &'static mut [Variable; MAX_ALIASES] {
+    unsafe { &mut *(&raw mut ALIASES) }
+}
+
+impl Variable {
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    fn value(&self) -> &str {
+        core::str::from_utf8(&self.value[..self.value_len]).unwrap_or("")
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= NAME_LEN
+        && !bytes[0].is_ascii_digit()
+        && bytes.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+fn find_in<'a>(table: &'a mut [Variable], name: &str) -> Option<&'a mut Variable> {
+    table.iter_mut().find(|v| v.used && v.name() == name)
+}
+
+fn store(table: &mut [Variable], name: &str, value: &str) -> Result<(), &'static str> {
+    if !is_valid_name(name) {
+        return Err("Invalid name");
+    }
+    if value.len() > VALUE_LEN {
+        return Err("Value too long");
+    }
+    let index = match table.iter().position(|v| v.used && v.name() == name) {
+        Some(index) => index,
+        None => {
+            let index = table.iter().position(|v| !v.used).ok_or("Table is full")?;
+            table[index] = EMPTY_VARIABLE;
+            table[index].name[..name.len()].copy_from_slice(name.as_bytes());
+            table[index].name_len = name.len();
+            table[index].used = true;
+            index
+        }
+    };
+    table[index].value[..value.len()].copy_from_slice(value.as_bytes());
+    table[index].value_len = value.len();
+    Ok(())
+}
+
+fn remove_from(table: &mut [Variable], name: &str) -> bool {
+    match find_in(table, name) {
+        Some(entry) => {
+            *entry = EMPTY_VARIABLE;
+            true
+        }
+        None => false,
+    }
+}
+
+fn find(name: &str) -> Option<&'static mut Variable> {
+    find_in(variables(), name)
+}
+
+pub fn get(name: &str) -> Option<&'static str> {
+    find(name).map(|v| v.value())
+}
+
+pub fn set(name: &str, value: &str) -> Result<(), &'static str> {
+    store(variables(), name, value)
+}
+
+pub fn unset(name: &str) -> bool {
+    remove_from(variables(), name)
+}
+
+pub fn alias(name: &str) -> Option<&'static str> {
+    find_in(aliases(), name).map(|a| a.value())
+}
+
+pub fn set_alias(name: &str, command: &str) -> Result<(), &'static str> {
+    store(aliases(), name, command)
+}
+
+pub fn remove_alias(name: &str) -> bool {
+    remove_from(aliases(), name)
+}
+
+pub fn init() {
+    for (name, value) in [("PS1", "anomia> "), ("HOME", "~"), ("EDITOR", "nano")] {
+        let _ = set(name, value);
+        let _ = export(name);
+    }
+}
+
+/// Gives console `to` a copy of the variables of console `from`.
+pub fn copy_variables(from: usize, to: usize) {
+    let tables = unsafe { &mut *(&raw mut VARIABLES) };
+    tables[to] = tables[from];
+}
+
+pub fn export(name: &str) -> Result<(), &'static str> {
+    if find(name).is_none() {
+        set(name, "")?;
+    }
+    if let Some(variable) = find(name) {
+        variable.exported = true;
+    }
+    Ok(())
+}
+
+pub fn for_each<F: FnMut(&str, &str, bool)>(mut f: F) {
+    for variable in variables().iter().filter(|v| v.used) {
+        f(variable.name(), variable.value(), variable.exported);
+    }
+}
+
+pub fn prompt() -> &'static str {
+    get("PS1").unwrap_or("anomia> ")
+}
+
+pub fn parse_assignment(arg: &str) -> Option<(&str, &str)> {
+    let (name, value) = arg.split_once('=')?;
+    if is_valid_name(name) { Some((name, value)) } else { None }
+}
+
+fn write_variable(io: &mut Io, prefix: &str, name: &str, value: &str) {
+    io.write_string(prefix);
+    io.write_string(name);
+    io.write_string("=");
+    io.write_string(value);
+    io.write_string("\n");
+}
+
+fn cmd_set(io: &mut Io, argv: &[&str]) -> i32 {
+    match argv.len() {
+        1 => {
+            for_each(|name, value, _| write_variable(io, "", name, value));
+            0
+        }
+        2 | 3 => {
+            let (name, value) = match (parse_assignment(argv[1]), argv.get(2)) {
+                (Some(assignment), None) => assignment,
+                (None, Some(&value)) => (argv[1], value),
+                _ => ("", ""),
+            };
+            match set(name, value) {
+                Ok(_) => 0,
+                Err(e) => {
+                    io.write_string("set: ");
+                    io.write_string(e);
+                    io.write_string("\n");
+                    1
+                }
+            }
+        }
+        _ => {
+            io.write_string("Usage: set [NAME=value | NAME value]\n");
+            2
+        }
+    }
+}
+
+fn cmd_export(io: &mut Io, argv: &[&str]) -> i32 {
+    if argv.len() == 1 {
+        for_each(|name, value, exported| {
+            if exported {
+                write_variable(io, "export ", name, value);
+            }
+        });
+        return 0;
+    }
+
+    let mut status = 0;
+    for &arg in &argv[1..] {
+        let result = match parse_assignment(arg) {
+            Some((name, value)) => set(name, value).and_then(|_| export(name)),
+            None if is_valid_name(arg) => export(arg),
+            None => Err("Invalid variable name"),
+        };
+        if let Err(e) = result {
+            io.write_string("export: ");
+            io.write_string(e);
+            io.write_string(": ");
+            io.write_string(arg);
+            io.write_string("\n");
+            status = 1;
+        }
+    }
+    status
+}
+
+fn cmd_env(io: &mut Io, argv: &[&str]) -> i32 {
+    if argv.len() > 1 {
+        io.write_string("Usage: env\n");
+        return 2;
+    }
+    for_each(|name, value, exported| {
+        if exported {
+            write_variable(io, "", name, value);
+        }
+    });
+    0
+}
+
+fn cmd_unset(io: &mut Io, argv: &[&str]) -> i32 {
+    if argv.len() < 2 {
+        io.write_string("Usage: unset <NAME>...\n");
+        return 2;
+    }
+    for &name in &argv[1..] {
+        unset(name);
+    }
+    0
+}
+
+fn cmd_alias(io: &mut Io, argv: &[&str]) -> i32 {
+    if argv.len() == 1 {
+        for entry in aliases().iter().filter(|a| a.used) {
+            io.write_string("alias ");
+            io.write_string(entry.name());
+            io.write_string("='");
+            io.write_string(entry.value());
+            io.write_string("'\n");
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for &arg in &argv[1..] {
+        let result = match arg.split_once('=') {
+            Some((name, command)) => set_alias(name, command),
+            None => match alias(arg) {
+                Some(command) => {
+                    io.write_string("alias ");
+                    io.write_string(arg);
+                    io.write_string("='");
+                    io.write_string(command);
+                    io.write_string("'\n");
+                    Ok(())
+                }
+                None => Err("Alias not found"),
+            },
+        };
+        if let Err(e) = result {
+            io.write_string("alias: ");
+            io.write_string(e);
+            io.write_string(": ");
+            io.write_string(arg);
+            io.write_string("\n");
+            status = 1;
+        }
+    }
+    status
+}
+
+fn cmd_unalias(io: &mut Io, argv: &[&str]) -> i32 {
+    if argv.len() < 2 {
+        io.write_string("Usage: unalias <name>...\n");
+        return 2;
+    }
+    let mut status = 0;
+    for &name in &argv[1..] {
+        if !remove_alias(name) {
+            io.write_string("unalias: not found: ");
+            io.write_string(name);
+            io.write_string("\n");
+            status = 1;
+        }
+    }
+    status
+}
+
+pub fn register_commands() {
+    commands::register_all(&[
+        Command {
+            name: "set",
+            aliases: &[],
+            usage: "set [NAME=value]",
+            summary: "List or set shell variables",
+            handler: cmd_set,
+        },
+        Command {
+            name: "export",
+            aliases: &[],
+            usage: "export [NAME[=value]]...",
+            summary: "Set and export environment variables",
+            handler: cmd_export,
+        },
+        Command {
+            name: "unset",
+            aliases: &[],
+            usage: "unset <NAME>...",
+            summary: "Remove shell variables",
+            handler: cmd_unset,
+        },
+        Command {
+            name: "env",
+            aliases: &[],
+            usage: "env",
+            summary: "List exported environment variables",
+            handler: cmd_env,
+        },
+        Command {
+            name: "alias",
+            aliases: &[],
+            usage: "alias [name=command]...",
+            summary: "List or define command aliases",
+            handler: cmd_alias,
+        },
+        Command {
+            name: "unalias",
+            aliases: &[],
+            usage: "unalias <name>...",
+            summary: "Remove command aliases",
+            handler: cmd_unalias,
+        },
+    ]);
+}
@@ -1,6 +1,11 @@
 #![allow(dead_code)]
 
+use crate::console::{self, CONSOLE_COUNT};
+
 const FOLDER_POOL_SIZE: usize = 32;
+// Room for a 256-glyph 8x16 PSF font with its Unicode table. The eight files take 48 KiB of
+// .bss, and commands that copy a file out (scripts, `>>`, tftp) use as much stack per copy.
+pub const MAX_FILE_SIZE: usize = 6144;
 #[derive(Debug)]
 pub enum FileSystemError {
     FileNotFound,
@@ -12,13 +17,14 @@ pub enum FileSystemError {
     PermissionDenied,
     DiskFull,
     NotADirectory,
+    Busy,
 }
 
 #[derive(Clone, Copy)]
 pub struct FileEntry {
     name: [u8; 32],      
     name_len: usize,     
-    data: [u8; 512],     
+    data: [u8; MAX_FILE_SIZE],
     data_len: usize,     
     exists: bool,        
 }
@@ -28,7 +34,6 @@ pub struct FolderEntry {
     name: [u8; 32],
     name_len: usize,
     exists: bool,
-    files: [FileEntry; 8],
     subfolders: [*mut FolderEntry; 4], 
 }
 
@@ -36,13 +41,6 @@ static mut FOLDER_POOL: [FolderEntry; FOLDER_POOL_SIZE] = [FolderEntry {
     name: [0; 32],
     name_len: 0,
     exists: false,
-    files: [FileEntry {
-        name: [0; 32],
-        name_len: 0,
-        data: [0; 512],
-        data_len: 0,
-        exists: false,
-    }; 8],
     subfolders: [core::ptr::null_mut(); 4],
 }; 32];
 
@@ -54,7 +52,6 @@ impl FolderEntry {
             name: [0; 32],
             name_len: 0,
             exists: false,
-            files: [FileEntry::new(); 8],
             subfolders: [core::ptr::null_mut(); 4],
         }
     }
@@ -151,7 +148,7 @@ impl FileEntry {
         Self {
             name: [0; 32],
             name_len: 0,
-            data: [0; 512],
+            data: [0; MAX_FILE_SIZE],
             data_len: 0,
             exists: false,
         }
@@ -201,11 +198,17 @@ impl FileEntry {
     }
 }
 
+#[derive(Clone, Copy)]
+struct WorkingDir {
+    folders: [*mut FolderEntry; 8],
+    depth: usize,
+}
+
 pub struct OsFileSystem {
     files: [FileEntry; 8],
     folders: [*mut FolderEntry; 4],
-    current_dir: [*mut FolderEntry; 8], 
-    current_dir_depth: usize, 
+    // One per console, so `cd` on one leaves the others where they were.
+    working_dirs: [WorkingDir; CONSOLE_COUNT],
 }
 
 impl OsFileSystem {
@@ -213,10 +216,22 @@ impl OsFileSystem {
         Self {
             files: [FileEntry::new(); 8],
             folders: [core::ptr::null_mut(); 4],
-            current_dir: [core::ptr::null_mut(); 8],
-            current_dir_depth: 0,
+            working_dirs: [WorkingDir { folders: [core::ptr::null_mut(); 8], depth: 0 }; CONSOLE_COUNT],
         }
     }
+
+    fn cwd(&self) -> &WorkingDir {
+        &self.working_dirs[console::active()]
+    }
+
+    fn cwd_mut(&mut self) -> &mut WorkingDir {
+        &mut self.working_dirs[console::active()]
+    }
+
+    /// Starts console `to` in the directory console `from` is in.
+    pub fn copy_working_dir(&mut self, from: usize, to: usize) {
+        self.working_dirs[to] = self.working_dirs[from];
+    }
 }
 
 static mut GLOBAL_FS: OsFileSystem = OsFileSystem::new();
@@ -235,16 +250,24 @@ impl Drop for OsFileSystem {
 }
 
 impl OsFileSystem {
+    /// Names of the folders leading to the current directory, outermost first.
+    pub fn current_path(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let cwd = self.cwd();
+        cwd.folders[..cwd.depth].iter().map(|&folder| unsafe { (*folder).get_name() })
+    }
+
     pub fn change_directory(&mut self, path: &str) -> Result<(), FileSystemError> {
         if path == ".." {
-            if self.current_dir_depth > 0 {
-                self.current_dir_depth -= 1;
+            let cwd = self.cwd_mut();
+            if cwd.depth > 0 {
+                cwd.depth -= 1;
                 return Ok(());
             }
             return Ok(()); 
         }
 
-        let target_folder = if self.current_dir_depth == 0 {
+        let cwd = *self.cwd();
+        let target_folder = if cwd.depth == 0 {
 
             if let Some(index) = self.folders.iter().position(|&f| !f.is_null() && unsafe { (*f).name_matches(path) }) {
                 unsafe { self.folders[index] }
@@ -253,7 +276,7 @@ impl OsFileSystem {
             }
         } else {
 
-            let current = self.current_dir[self.current_dir_depth - 1];
+            let current = cwd.folders[cwd.depth - 1];
             if let Some(index) = unsafe { (*current).find_subfolder(path) } {
                 unsafe { (*current).subfolders[index] }
             } else {
@@ -261,12 +284,13 @@ impl OsFileSystem {
             }
         };
 
-        if self.current_dir_depth >= self.current_dir.len() {
+        if cwd.depth >= cwd.folders.len() {
             return Err(FileSystemError::InvalidPath);
         }
 
-        self.current_dir[self.current_dir_depth] = target_folder;
-        self.current_dir_depth += 1;
+        let cwd = self.cwd_mut();
+        cwd.folders[cwd.depth] = target_folder;
+        cwd.depth += 1;
         Ok(())
     }
 
@@ -275,7 +299,8 @@ impl OsFileSystem {
         let mut files = [None; 8];
         let mut folder_count = 0;
 
-        if self.current_dir_depth == 0 {
+        let cwd = self.cwd();
+        if cwd.depth == 0 {
 
             for (i, &folder) in self.folders.iter().enumerate() {
                 if !folder.is_null() {
@@ -291,7 +316,7 @@ impl OsFileSystem {
             }
         } else {
 
-            let current = unsafe { &*self.current_dir[self.current_dir_depth - 1] };
+            let current = unsafe { &*cwd.folders[cwd.depth - 1] };
             for (i, &subfolder) in current.subfolders.iter().enumerate() {
                 if !subfolder.is_null() {
                     unsafe {
@@ -309,6 +334,62 @@ impl OsFileSystem {
         (folders, files)
     }
 
+    fn resolve_folder(&self, path: &str) -> Result<Option<*mut FolderEntry>, FileSystemError> {
+        let mut stack = [core::ptr::null_mut(); 8];
+        let mut depth = 0;
+        if !path.starts_with('/') {
+            let cwd = self.cwd();
+            stack[..cwd.depth].copy_from_slice(&cwd.folders[..cwd.depth]);
+            depth = cwd.depth;
+        }
+
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => depth = depth.saturating_sub(1),
+                name => {
+                    let next = if depth == 0 {
+                        self.folders
+                            .iter()
+                            .copied()
+                            .find(|&f| !f.is_null() && unsafe { (*f).exists && (*f).name_matches(name) })
+                    } else {
+                        let current = stack[depth - 1];
+                        unsafe { (*current).find_subfolder(name).map(|index| (*current).subfolders[index]) }
+                    };
+                    let next = next.ok_or(FileSystemError::FileNotFound)?;
+                    if depth >= stack.len() {
+                        return Err(FileSystemError::InvalidPath);
+                    }
+                    stack[depth] = next;
+                    depth += 1;
+                }
+            }
+        }
+
+        Ok(if depth == 0 { None } else { Some(stack[depth - 1]) })
+    }
+
+    pub fn list_directory(&self, path: &str) -> Result<[Option<&[u8]>; 4], FileSystemError> {
+        let mut folders = [None; 4];
+        let mut count = 0;
+        let entries: &[*mut FolderEntry] = match self.resolve_folder(path)? {
+            Some(folder) => unsafe { &(*folder).subfolders },
+            None => &self.folders,
+        };
+        for &folder in entries {
+            if !folder.is_null() {
+                unsafe {
+                    if (*folder).exists && count < folders.len() {
+                        folders[count] = Some((*folder).get_name());
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(folders)
+    }
+
     fn find_file(&self, path: &str) -> Option<usize> {
         for (index, file) in self.files.iter().enumerate() {
             if file.exists && file.name_matches(path) {
@@ -467,6 +548,11 @@ impl OsFileSystem {
             }
         }
 
+        // A console still inside the folder, or below it, would be left pointing at a freed entry.
+        if current_folder.is_some_and(|folder| self.working_dirs.iter().any(|cwd| cwd.folders[..cwd.depth].contains(&folder))) {
+            return Err(FileSystemError::Busy);
+        }
+
         unsafe {
             if let Some(parent_ptr) = parent_folder {
                 (*parent_ptr).remove_subfolder(folder_name)
@@ -0,0 +1,173 @@
+use crate::commands::{self, Command};
+use crate::io::{Io, Sink};
+use crate::{cp437, file_system, graphics, settings};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_HEADER_SIZE: usize = 4;
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQUENCE: u16 = 0xFFFE;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HEADER_SIZE: usize = 32;
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+
+/// Maps characters to glyph numbers, as stored after the glyphs of a PSF file.
+enum UnicodeTable<'a> {
+    /// UCS-2 little endian, each glyph's list ending with 0xFFFF.
+    Psf1(&'a [u8]),
+    /// UTF-8, each glyph's list ending with 0xFF.
+    Psf2(&'a [u8]),
+}
+
+impl UnicodeTable<'_> {
+    /// Glyph that draws `c` on its own; multi-character sequences are skipped.
+    fn find(&self, c: char) -> Option<usize> {
+        let mut glyph = 0;
+        let mut in_sequence = false;
+        match self {
+            UnicodeTable::Psf1(table) => {
+                for pair in table.chunks_exact(2) {
+                    match u16::from_le_bytes([pair[0], pair[1]]) {
+                        PSF1_SEPARATOR => {
+                            glyph += 1;
+                            in_sequence = false;
+                        }
+                        PSF1_START_SEQUENCE => in_sequence = true,
+                        value if !in_sequence && value as u32 == c as u32 => return Some(glyph),
+                        _ => {}
+                    }
+                }
+            }
+            UnicodeTable::Psf2(table) => {
+                let mut decoder = cp437::Utf8Decoder::new();
+                for &byte in table.iter() {
+                    match byte {
+                        PSF2_SEPARATOR => {
+                            glyph += 1;
+                            in_sequence = false;
+                            decoder = cp437::Utf8Decoder::new();
+                        }
+                        PSF2_START_SEQUENCE => in_sequence = true,
+                        _ => {
+                            if decoder.feed(byte) == Some(c) && !in_sequence {
+                                return Some(glyph);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+pub struct Font<'a> {
+    glyphs: &'a [u8],
+    count: usize,
+    bytes_per_glyph: usize,
+    pub height: usize,
+    unicode: Option<UnicodeTable<'a>>,
+}
+
+impl<'a> Font<'a> {
+    /// Rows of the glyph for a CP437 code. Fonts with a Unicode table are looked up by character,
+    /// others are assumed to be in CP437 order.
+    pub fn glyph(&self, code: u8) -> Option<&'a [u8]> {
+        let index = match &self.unicode {
+            Some(table) => table.find(cp437::to_char(code))?,
+            None => code as usize,
+        };
+        if index >= self.count {
+            return None;
+        }
+        Some(&self.glyphs[index * self.bytes_per_glyph..][..self.height])
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize
+}
+
+/// Parses a PSF1 or PSF2 font 8 pixels wide.
+pub fn parse(data: &[u8]) -> Result<Font<'_>, &'static str> {
+    if data.starts_with(&PSF1_MAGIC) && data.len() >= PSF1_HEADER_SIZE {
+        let mode = data[2];
+        let height = data[3] as usize;
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let end = PSF1_HEADER_SIZE + count * height;
+        let glyphs = data.get(PSF1_HEADER_SIZE..end).ok_or("truncated glyph data")?;
+        let unicode = (mode & PSF1_MODE_HAS_TABLE != 0).then(|| UnicodeTable::Psf1(&data[end..]));
+        return Ok(Font { glyphs, count, bytes_per_glyph: height, height, unicode });
+    }
+
+    if data.starts_with(&PSF2_MAGIC) && data.len() >= PSF2_HEADER_SIZE {
+        let header_size = read_u32(data, 8);
+        let flags = read_u32(data, 12) as u32;
+        let count = read_u32(data, 16);
+        let bytes_per_glyph = read_u32(data, 20);
+        let height = read_u32(data, 24);
+        let width = read_u32(data, 28);
+        if width > 8 {
+            return Err("only fonts up to 8 pixels wide fit the VGA text modes");
+        }
+        if bytes_per_glyph < height {
+            return Err("bad glyph size");
+        }
+        let end = count.checked_mul(bytes_per_glyph).and_then(|size| size.checked_add(header_size)).ok_or("bad glyph count")?;
+        let glyphs = data.get(header_size..end).ok_or("truncated glyph data")?;
+        let unicode = (flags & PSF2_HAS_UNICODE_TABLE != 0).then(|| UnicodeTable::Psf2(&data[end..]));
+        return Ok(Font { glyphs, count, bytes_per_glyph, height, unicode });
+    }
+
+    Err("not a PSF font")
+}
+
+/// Loads a PSF font from the file system into the VGA.
+pub fn load(path: &str) -> Result<(), &'static str> {
+    file_system::with_fs(|fs| {
+        let data = fs.read_file(path).map_err(|_| "no such file")?;
+        let font = parse(data)?;
+        graphics::set_font(font.height, |code| font.glyph(code))
+    })
+}
+
+fn cmd_setfont(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut settings = settings::get_settings();
+    match argv.get(1).copied() {
+        None => {
+            io.write_string(settings.console_font().unwrap_or("default (BIOS)"));
+            io.write_string("\n");
+            0
+        }
+        Some("-d") => {
+            graphics::reset_font();
+            settings.clear_console_font();
+            settings::set_settings(settings);
+            0
+        }
+        Some(path) => match settings.set_console_font(path).and_then(|_| load(path)) {
+            Ok(()) => {
+                settings::set_settings(settings);
+                0
+            }
+            Err(e) => {
+                writeln!(io, "setfont: {}: {}", path, e);
+                1
+            }
+        },
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[Command {
+        name: "setfont",
+        aliases: &[],
+        usage: "setfont [file.psf | -d]",
+        summary: "Load a PSF console font (-d restores the default)",
+        handler: cmd_setfont,
+    }]);
+}
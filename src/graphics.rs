@@ -0,0 +1,731 @@
+use crate::commands::{self, Command};
+use crate::io::{Io, Sink};
+use crate::port::{inb, outb};
+use crate::vga_buffer::{self, Buffer, Color};
+use crate::{console, cp437, interrupts, keyboard, memory};
+
+const MISC_WRITE: u16 = 0x3C2;
+const SEQ_INDEX: u16 = 0x3C4;
+const SEQ_DATA: u16 = 0x3C5;
+const GC_INDEX: u16 = 0x3CE;
+const GC_DATA: u16 = 0x3CF;
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+const AC_INDEX: u16 = 0x3C0;
+const INPUT_STATUS: u16 = 0x3DA;
+const DAC_READ_INDEX: u16 = 0x3C7;
+const DAC_WRITE_INDEX: u16 = 0x3C8;
+const DAC_DATA: u16 = 0x3C9;
+
+const SEQ_MAP_MASK: u8 = 0x02;
+const SEQ_MEMORY_MODE: u8 = 0x04;
+const GC_SET_RESET: u8 = 0x00;
+const GC_READ_MAP: u8 = 0x04;
+const GC_MODE: u8 = 0x05;
+const GC_MISC: u8 = 0x06;
+const GC_BIT_MASK: u8 = 0x08;
+const CRTC_END_HORIZONTAL_BLANK: u8 = 0x03;
+const CRTC_VERTICAL_RETRACE_END: u8 = 0x11;
+const AC_VIDEO_ENABLE: u8 = 0x20;
+
+const VGA_MEMORY: u64 = 0xA0000;
+const PALETTE_SIZE: usize = 256 * 3;
+
+pub const FONT_WIDTH: usize = 8;
+pub const FONT_HEIGHT: usize = 16;
+const SMALL_FONT_HEIGHT: usize = 8;
+// Plane 2 reserves 32 bytes per glyph whatever the font height.
+const FONT_STRIDE: usize = 32;
+
+const SEQ_COUNT: usize = 5;
+const CRTC_COUNT: usize = 25;
+const GC_COUNT: usize = 9;
+const AC_COUNT: usize = 21;
+
+// Miscellaneous output, then the sequencer, CRTC, graphics and attribute controller registers.
+type ModeRegisters = [u8; 1 + SEQ_COUNT + CRTC_COUNT + GC_COUNT + AC_COUNT];
+
+// All text modes use a CRTC offset (register 0x13) of 0x2D, so every row is vga_buffer::MAX_WIDTH cells long.
+const TEXT_80X25: ModeRegisters = [
+    0x67,
+    0x03, 0x00, 0x03, 0x00, 0x02,
+    0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00, 0x50,
+    0x9C, 0x0E, 0x8F, 0x2D, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+// 80x25 timing with 8-line characters.
+const TEXT_80X50: ModeRegisters = [
+    0x67,
+    0x03, 0x00, 0x03, 0x00, 0x02,
+    0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00, 0x50,
+    0x9C, 0x0E, 0x8F, 0x2D, 0x1F, 0x96, 0xB9, 0xA3, 0xFF,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+// 480 lines on the 28 MHz clock with 8-pixel-wide, 8-line characters.
+const TEXT_90X60: ModeRegisters = [
+    0xE7,
+    0x03, 0x01, 0x03, 0x00, 0x02,
+    0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E, 0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00, 0x00,
+    0xEA, 0x0C, 0xDF, 0x2D, 0x08, 0xE8, 0x05, 0xA3, 0xFF,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+const MODE_13H: ModeRegisters = [
+    0x63,
+    0x03, 0x01, 0x0F, 0x00, 0x0E,
+    0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0xBF, 0x1F, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x9C, 0x0E, 0x8F, 0x28, 0x40, 0x96, 0xB9, 0xA3, 0xFF,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0F, 0xFF,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x41, 0x00, 0x0F, 0x00, 0x00,
+];
+
+// Unlike the BIOS mode, set/reset is enabled on all four planes so put_pixel only has to load the color.
+const MODE_12H: ModeRegisters = [
+    0xE3,
+    0x03, 0x01, 0x0F, 0x00, 0x06,
+    0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0x0B, 0x3E, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xEA, 0x0C, 0xDF, 0x28, 0x00, 0xE7, 0x04, 0xE3, 0xFF,
+    0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x05, 0x0F, 0xFF,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x01, 0x00, 0x0F, 0x00, 0x00,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Text80x25,
+    Text80x50,
+    Text90x60,
+    /// 320x200, 256 colors, one byte per pixel.
+    Mode13h,
+    /// 640x480, 16 colors, four bit planes.
+    Mode12h,
+}
+
+pub const TEXT_MODES: [Mode; 3] = [Mode::Text80x25, Mode::Text80x50, Mode::Text90x60];
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "80x25" => Some(Mode::Text80x25),
+            "80x50" => Some(Mode::Text80x50),
+            "90x60" => Some(Mode::Text90x60),
+            "13h" => Some(Mode::Mode13h),
+            "12h" => Some(Mode::Mode12h),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Text80x25 => "80x25",
+            Mode::Text80x50 => "80x50",
+            Mode::Text90x60 => "90x60",
+            Mode::Mode13h => "13h",
+            Mode::Mode12h => "12h",
+        }
+    }
+
+    pub fn is_text(self) -> bool {
+        TEXT_MODES.contains(&self)
+    }
+
+    /// The text mode after this one, for cycling through them in the settings menu.
+    pub fn next_text(self) -> Mode {
+        let index = TEXT_MODES.iter().position(|&mode| mode == self).map_or(0, |index| index + 1);
+        TEXT_MODES[index % TEXT_MODES.len()]
+    }
+
+    fn registers(self) -> &'static ModeRegisters {
+        match self {
+            Mode::Text80x25 => &TEXT_80X25,
+            Mode::Text80x50 => &TEXT_80X50,
+            Mode::Text90x60 => &TEXT_90X60,
+            Mode::Mode13h => &MODE_13H,
+            Mode::Mode12h => &MODE_12H,
+        }
+    }
+
+    /// Columns and rows for text modes, pixels for graphics modes.
+    fn size(self) -> (usize, usize) {
+        match self {
+            Mode::Text80x25 => (80, 25),
+            Mode::Text80x50 => (80, 50),
+            Mode::Text90x60 => (90, 60),
+            Mode::Mode13h => (320, 200),
+            Mode::Mode12h => (640, 480),
+        }
+    }
+
+    fn char_height(self) -> usize {
+        if self == Mode::Text80x25 { 16 } else { 8 }
+    }
+
+    fn colors(self) -> usize {
+        if self == Mode::Mode13h { 256 } else { 16 }
+    }
+}
+
+static mut CURRENT: Mode = Mode::Text80x25;
+static mut TEXT_MODE: Mode = Mode::Text80x25;
+static mut BIOS_FONT: [u8; 256 * FONT_HEIGHT] = [0; 256 * FONT_HEIGHT];
+static mut FONT: [u8; 256 * FONT_HEIGHT] = [0; 256 * FONT_HEIGHT];
+// A font loaded for the 8-line modes; until then they use rows merged from FONT.
+static mut SMALL_FONT: Option<[u8; 256 * SMALL_FONT_HEIGHT]> = None;
+static mut TEXT_PALETTE: [u8; PALETTE_SIZE] = [0; PALETTE_SIZE];
+static mut TEXT_SCREEN: Buffer = unsafe { core::mem::zeroed() };
+
+fn seq_read(index: u8) -> u8 {
+    outb(SEQ_INDEX, index);
+    inb(SEQ_DATA)
+}
+
+fn seq_write(index: u8, value: u8) {
+    outb(SEQ_INDEX, index);
+    outb(SEQ_DATA, value);
+}
+
+fn gc_read(index: u8) -> u8 {
+    outb(GC_INDEX, index);
+    inb(GC_DATA)
+}
+
+fn gc_write(index: u8, value: u8) {
+    outb(GC_INDEX, index);
+    outb(GC_DATA, value);
+}
+
+fn crtc_read(index: u8) -> u8 {
+    outb(CRTC_INDEX, index);
+    inb(CRTC_DATA)
+}
+
+fn crtc_write(index: u8, value: u8) {
+    outb(CRTC_INDEX, index);
+    outb(CRTC_DATA, value);
+}
+
+fn write_registers(registers: &ModeRegisters) {
+    let (seq, rest) = registers[1..].split_at(SEQ_COUNT);
+    let (crtc, rest) = rest.split_at(CRTC_COUNT);
+    let (gc, ac) = rest.split_at(GC_COUNT);
+
+    outb(MISC_WRITE, registers[0]);
+    for (index, &value) in seq.iter().enumerate() {
+        seq_write(index as u8, value);
+    }
+
+    // CRTC registers 0-7 are write-protected until bit 7 of the vertical retrace end is cleared.
+    crtc_write(CRTC_END_HORIZONTAL_BLANK, crtc_read(CRTC_END_HORIZONTAL_BLANK) | 0x80);
+    crtc_write(CRTC_VERTICAL_RETRACE_END, crtc_read(CRTC_VERTICAL_RETRACE_END) & !0x80);
+    for (index, &value) in crtc.iter().enumerate() {
+        let value = match index as u8 {
+            CRTC_END_HORIZONTAL_BLANK => value | 0x80,
+            CRTC_VERTICAL_RETRACE_END => value & !0x80,
+            _ => value,
+        };
+        crtc_write(index as u8, value);
+    }
+
+    for (index, &value) in gc.iter().enumerate() {
+        gc_write(index as u8, value);
+    }
+
+    // Reading the input status resets the attribute controller to expect an index.
+    for (index, &value) in ac.iter().enumerate() {
+        inb(INPUT_STATUS);
+        outb(AC_INDEX, index as u8);
+        outb(AC_INDEX, value);
+    }
+    inb(INPUT_STATUS);
+    outb(AC_INDEX, AC_VIDEO_ENABLE);
+}
+
+fn vga_memory() -> *mut u8 {
+    memory::phys_to_virt(VGA_MEMORY)
+}
+
+/// Maps font plane 2 linearly at 0xA0000 while `f` runs, as text mode hides it behind odd/even addressing.
+fn with_font_plane<F: FnOnce(*mut u8)>(f: F) {
+    // Text written meanwhile, such as the status bar's, would land in the font.
+    interrupts::without_interrupts(|| map_font_plane(f));
+}
+
+fn map_font_plane<F: FnOnce(*mut u8)>(f: F) {
+    let map_mask = seq_read(SEQ_MAP_MASK);
+    let memory_mode = seq_read(SEQ_MEMORY_MODE);
+    let read_map = gc_read(GC_READ_MAP);
+    let gc_mode = gc_read(GC_MODE);
+    let gc_misc = gc_read(GC_MISC);
+
+    seq_write(SEQ_MAP_MASK, 0x04);
+    seq_write(SEQ_MEMORY_MODE, 0x06);
+    gc_write(GC_READ_MAP, 0x02);
+    gc_write(GC_MODE, 0x00);
+    gc_write(GC_MISC, 0x04);
+
+    f(vga_memory());
+
+    seq_write(SEQ_MAP_MASK, map_mask);
+    seq_write(SEQ_MEMORY_MODE, memory_mode);
+    gc_write(GC_READ_MAP, read_map);
+    gc_write(GC_MODE, gc_mode);
+    gc_write(GC_MISC, gc_misc);
+}
+
+fn font() -> &'static mut [u8; 256 * FONT_HEIGHT] {
+    unsafe { &mut *(&raw mut FONT) }
+}
+
+fn small_font() -> &'static mut Option<[u8; 256 * SMALL_FONT_HEIGHT]> {
+    unsafe { &mut *(&raw mut SMALL_FONT) }
+}
+
+fn save_font() {
+    let bios_font = unsafe { &mut *(&raw mut BIOS_FONT) };
+    with_font_plane(|plane| {
+        for glyph in 0..256 {
+            for row in 0..FONT_HEIGHT {
+                bios_font[glyph * FONT_HEIGHT + row] = unsafe { plane.add(glyph * FONT_STRIDE + row).read_volatile() };
+            }
+        }
+    });
+    *font() = *bios_font;
+}
+
+/// Row `row` of a glyph shrunk to `char_height` rows by merging neighbouring rows.
+fn merged_row(bitmap: &[u8], row: usize, char_height: usize) -> u8 {
+    let scale = FONT_HEIGHT / char_height;
+    bitmap[row * scale..][..scale].iter().fold(0, |bits, &line| bits | line)
+}
+
+/// Loads the console font into plane 2 for a mode with `char_height`-line characters.
+fn load_font(char_height: usize) {
+    let font = font();
+    let small_font = small_font().as_ref().filter(|_| char_height == SMALL_FONT_HEIGHT);
+    with_font_plane(|plane| {
+        for glyph in 0..256 {
+            let bitmap = &font[glyph * FONT_HEIGHT..][..FONT_HEIGHT];
+            for row in 0..FONT_STRIDE {
+                let bits = match small_font {
+                    _ if row >= char_height => 0,
+                    Some(small_font) => small_font[glyph * SMALL_FONT_HEIGHT + row],
+                    None => merged_row(bitmap, row, char_height),
+                };
+                unsafe { plane.add(glyph * FONT_STRIDE + row).write_volatile(bits) };
+            }
+        }
+    });
+}
+
+fn reload_font() {
+    let mode = current_mode();
+    if mode.is_text() {
+        load_font(mode.char_height());
+    }
+}
+
+/// Replaces console glyphs with ones `height` rows high. `glyph` returns the rows for a CP437
+/// code, or None to keep the current glyph. 8-row fonts serve the 8-line text modes; taller
+/// ones are centered in the 16-row font used by 80x25 and the graphics modes.
+pub fn set_font<'a, F: Fn(u8) -> Option<&'a [u8]>>(height: usize, glyph: F) -> Result<(), &'static str> {
+    let font = font();
+    if height == SMALL_FONT_HEIGHT {
+        let small_font = small_font().get_or_insert_with(|| {
+            let mut small_font = [0; 256 * SMALL_FONT_HEIGHT];
+            for (index, bits) in small_font.iter_mut().enumerate() {
+                let glyph = index / SMALL_FONT_HEIGHT;
+                *bits = merged_row(&font[glyph * FONT_HEIGHT..][..FONT_HEIGHT], index % SMALL_FONT_HEIGHT, SMALL_FONT_HEIGHT);
+            }
+            small_font
+        });
+        for code in 0..=255u8 {
+            if let Some(rows) = glyph(code) {
+                small_font[code as usize * SMALL_FONT_HEIGHT..][..SMALL_FONT_HEIGHT].copy_from_slice(&rows[..SMALL_FONT_HEIGHT]);
+            }
+        }
+    } else if height > SMALL_FONT_HEIGHT && height <= FONT_HEIGHT {
+        let top = (FONT_HEIGHT - height) / 2;
+        for code in 0..=255u8 {
+            if let Some(rows) = glyph(code) {
+                let bitmap = &mut font[code as usize * FONT_HEIGHT..][..FONT_HEIGHT];
+                bitmap.fill(0);
+                bitmap[top..top + height].copy_from_slice(&rows[..height]);
+            }
+        }
+    } else {
+        return Err("font height must be between 8 and 16 rows");
+    }
+    reload_font();
+    Ok(())
+}
+
+/// Goes back to the font the BIOS left in plane 2 at boot.
+pub fn reset_font() {
+    *font() = unsafe { BIOS_FONT };
+    *small_font() = None;
+    reload_font();
+}
+
+fn read_palette(palette: &mut [u8; PALETTE_SIZE]) {
+    outb(DAC_READ_INDEX, 0);
+    for value in palette.iter_mut() {
+        *value = inb(DAC_DATA);
+    }
+}
+
+fn write_palette(palette: &[u8; PALETTE_SIZE]) {
+    outb(DAC_WRITE_INDEX, 0);
+    for &value in palette {
+        outb(DAC_DATA, value);
+    }
+}
+
+/// RGB (6 bits per channel) of an EGA color number `rgbRGB`, as the BIOS sets up the DAC.
+fn ega_color(value: u8) -> [u8; 3] {
+    let level = |primary: u8, secondary: u8| (value >> primary & 1) * 0x2A + (value >> secondary & 1) * 0x15;
+    [level(2, 5), level(1, 4), level(0, 3)]
+}
+
+/// The 16 text colors, a 6x6x6 color cube and a 24-step gray ramp.
+fn write_256_color_palette() {
+    let text_colors = &TEXT_80X25[1 + SEQ_COUNT + CRTC_COUNT + GC_COUNT..][..16];
+    let cube = |step: usize| (step * 63 / 5) as u8;
+    outb(DAC_WRITE_INDEX, 0);
+    for index in 0..256 {
+        let rgb = match index {
+            0..16 => ega_color(text_colors[index]),
+            16..232 => [cube((index - 16) / 36), cube((index - 16) / 6 % 6), cube((index - 16) % 6)],
+            _ => [((index - 232) * 63 / 23) as u8; 3],
+        };
+        for value in rgb {
+            outb(DAC_DATA, value);
+        }
+    }
+}
+
+fn save_text_state() {
+    let writer = console::writer();
+    console::copy_screen(writer.buffer, unsafe { &mut *(&raw mut TEXT_SCREEN) });
+    read_palette(unsafe { &mut *(&raw mut TEXT_PALETTE) });
+}
+
+fn restore_text_state() {
+    write_palette(unsafe { &*(&raw const TEXT_PALETTE) });
+    let writer = console::writer();
+    console::copy_screen(unsafe { &*(&raw const TEXT_SCREEN) }, writer.buffer);
+}
+
+/// Keeps a copy of the BIOS font for drawing and for the 8-line modes, and reprograms 80x25
+/// with the row length the text buffer expects. Runs before anything is printed.
+pub fn init() {
+    save_font();
+    write_registers(Mode::Text80x25.registers());
+}
+
+pub fn current_mode() -> Mode {
+    unsafe { CURRENT }
+}
+
+/// The text mode in use, or the one to return to from graphics.
+pub fn text_mode() -> Mode {
+    unsafe { TEXT_MODE }
+}
+
+/// Reprograms the VGA for `mode`. Leaving text mode saves the screen and palette, and
+/// returning to it restores them. Returns the framebuffer for graphics modes.
+pub fn set_mode(mode: Mode) -> Option<Framebuffer> {
+    // The status bar must not draw into the text buffer while the mode changes under it.
+    interrupts::without_interrupts(|| switch_mode(mode))
+}
+
+fn switch_mode(mode: Mode) -> Option<Framebuffer> {
+    let current = current_mode();
+    if mode == current {
+        return framebuffer();
+    }
+    if current.is_text() && !mode.is_text() {
+        save_text_state();
+    }
+
+    write_registers(mode.registers());
+    unsafe { CURRENT = mode };
+    if mode.is_text() {
+        load_font(mode.char_height());
+        if !current.is_text() {
+            restore_text_state();
+        }
+        unsafe { TEXT_MODE = mode };
+        let (columns, rows) = mode.size();
+        vga_buffer::set_screen_size(columns, rows, mode.char_height() as u8);
+        console::resize();
+        return None;
+    }
+
+    if mode == Mode::Mode13h {
+        write_256_color_palette();
+    } else {
+        write_palette(unsafe { &*(&raw const TEXT_PALETTE) });
+    }
+    let mut framebuffer = framebuffer()?;
+    framebuffer.clear(0);
+    Some(framebuffer)
+}
+
+pub fn framebuffer() -> Option<Framebuffer> {
+    let mode = current_mode();
+    if mode.is_text() {
+        return None;
+    }
+    let (width, height) = mode.size();
+    Some(Framebuffer { mode, width, height, memory: vga_memory() })
+}
+
+/// Drawing surface of the current graphics mode. Coordinates outside it are clipped.
+pub struct Framebuffer {
+    mode: Mode,
+    pub width: usize,
+    pub height: usize,
+    memory: *mut u8,
+}
+
+impl Framebuffer {
+    pub fn colors(&self) -> usize {
+        self.mode.colors()
+    }
+
+    pub fn put_pixel(&mut self, x: i32, y: i32, color: u8) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        match self.mode {
+            Mode::Mode13h => unsafe { self.memory.add(y * self.width + x).write_volatile(color) },
+            Mode::Mode12h => {
+                // Set/reset supplies the color to all four planes; the read loads the latches
+                // so the bit mask keeps the seven neighbouring pixels.
+                gc_write(GC_SET_RESET, color & 0x0F);
+                gc_write(GC_BIT_MASK, 0x80 >> (x % 8));
+                let address = unsafe { self.memory.add(y * self.width / 8 + x / 8) };
+                unsafe {
+                    address.read_volatile();
+                    address.write_volatile(0xFF);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn clear(&mut self, color: u8) {
+        match self.mode {
+            Mode::Mode13h => {
+                for offset in 0..self.width * self.height {
+                    unsafe { self.memory.add(offset).write_volatile(color) };
+                }
+            }
+            Mode::Mode12h => {
+                gc_write(GC_SET_RESET, color & 0x0F);
+                gc_write(GC_BIT_MASK, 0xFF);
+                for offset in 0..self.width * self.height / 8 {
+                    unsafe { self.memory.add(offset).write_volatile(0xFF) };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            self.put_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    pub fn rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        self.line(x, y, right, y, color);
+        self.line(x, bottom, right, bottom, color);
+        self.line(x, y, x, bottom, color);
+        self.line(right, y, right, bottom, color);
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        for row in y..y + height {
+            for col in x..x + width {
+                self.put_pixel(col, row, color);
+            }
+        }
+    }
+
+    /// Copies a `width`-pixel-wide image, one color byte per pixel, with its top-left corner at (x, y).
+    pub fn blit(&mut self, x: i32, y: i32, width: usize, pixels: &[u8]) {
+        if width == 0 {
+            return;
+        }
+        for (row, line) in pixels.chunks(width).enumerate() {
+            for (col, &color) in line.iter().enumerate() {
+                self.put_pixel(x + col as i32, y + row as i32, color);
+            }
+        }
+    }
+
+    /// Draws a CP437 glyph from the 8x16 console font. A `background` of None leaves it transparent.
+    pub fn draw_glyph(&mut self, x: i32, y: i32, glyph: u8, foreground: u8, background: Option<u8>) {
+        let bitmap = &font()[glyph as usize * FONT_HEIGHT..][..FONT_HEIGHT];
+        for (row, &bits) in bitmap.iter().enumerate() {
+            for col in 0..FONT_WIDTH {
+                let y = y + row as i32;
+                let x = x + col as i32;
+                if bits & (0x80 >> col) != 0 {
+                    self.put_pixel(x, y, foreground);
+                } else if let Some(background) = background {
+                    self.put_pixel(x, y, background);
+                }
+            }
+        }
+    }
+
+    /// Draws UTF-8 text starting at (x, y); `\n` starts a new line below.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, foreground: u8, background: Option<u8>) {
+        let mut decoder = cp437::Utf8Decoder::new();
+        let (mut col, mut row) = (x, y);
+        for &byte in text.as_bytes() {
+            if byte == b'\n' {
+                col = x;
+                row += FONT_HEIGHT as i32;
+                continue;
+            }
+            if let Some(glyph) = decoder.feed_glyph(byte) {
+                self.draw_glyph(col, row, glyph, foreground, background);
+                col += FONT_WIDTH as i32;
+            }
+        }
+    }
+}
+
+const BALL: [u8; 64] = [
+    0, 0, 4, 4, 4, 4, 0, 0,
+    0, 4, 12, 12, 12, 12, 4, 0,
+    4, 12, 15, 15, 12, 12, 12, 4,
+    4, 12, 15, 12, 12, 12, 12, 4,
+    4, 12, 12, 12, 12, 12, 12, 4,
+    4, 12, 12, 12, 12, 12, 12, 4,
+    0, 4, 12, 12, 12, 12, 4, 0,
+    0, 0, 4, 4, 4, 4, 0, 0,
+];
+
+fn draw_demo(framebuffer: &mut Framebuffer) {
+    let width = framebuffer.width as i32;
+    let height = framebuffer.height as i32;
+    let colors = framebuffer.colors() as i32;
+    let white = Color::White as u8;
+
+    framebuffer.rect(0, 0, width, height, Color::LightCyan as u8);
+    let title = if framebuffer.mode == Mode::Mode13h { "Anomia OS - mode 13h, 320x200x256" } else { "Anomia OS - mode 12h, 640x480x16" };
+    framebuffer.draw_text(8, 4, title, white, None);
+
+    // A fan of lines in the left half, nested rectangles with bouncing balls in the right.
+    let (left, top, bottom) = (8, 24, height - 48);
+    let middle = width / 2;
+    for i in 0..=16 {
+        let color = (1 + i % 15) as u8;
+        framebuffer.line(left, bottom, left + i * (middle - 2 * left) / 16, top, color);
+        framebuffer.line(left, bottom, middle - left, top + i * (bottom - top) / 16, color);
+    }
+    for i in 0..8 {
+        let inset = i * (bottom - top) / 20;
+        framebuffer.rect(middle + left + inset, top + inset, middle - 2 * left - 2 * inset, bottom - top - 2 * inset, (9 + i % 7) as u8);
+    }
+    for i in 0..4 {
+        framebuffer.blit(middle + width / 8 + i * width / 12, top + (bottom - top) / 2 - 4 + (i % 2) * 12, 8, &BALL);
+    }
+
+    // Every color of the palette.
+    let swatch = (width - 2 * left) / colors;
+    for color in 0..colors {
+        framebuffer.fill_rect(left + color * swatch, height - 40, swatch, 16, color as u8);
+    }
+    framebuffer.draw_text(8, height - 20, "Press any key to return", Color::Yellow as u8, None);
+}
+
+fn cmd_gfx(io: &mut Io, argv: &[&str]) -> i32 {
+    let mode = Mode::from_name(argv.get(1).copied().unwrap_or("13h"));
+    let Some(mode) = mode.filter(|mode| !mode.is_text()) else {
+        io.write_string("Usage: gfx [13h|12h]\n");
+        return 2;
+    };
+    if let Some(mut framebuffer) = set_mode(mode) {
+        draw_demo(&mut framebuffer);
+        keyboard::read_key();
+    }
+    set_mode(text_mode());
+    0
+}
+
+fn cmd_mode(io: &mut Io, argv: &[&str]) -> i32 {
+    let Some(&name) = argv.get(1) else {
+        for mode in TEXT_MODES {
+            io.write_string(if mode == text_mode() { "* " } else { "  " });
+            io.write_string(mode.name());
+            io.write_string("\n");
+        }
+        return 0;
+    };
+    match Mode::from_name(name).filter(|mode| mode.is_text()) {
+        Some(mode) => {
+            set_mode(mode);
+            0
+        }
+        None => {
+            writeln!(io, "mode: unknown text mode '{}' (80x25, 80x50 or 90x60)", name);
+            2
+        }
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[
+        Command {
+            name: "gfx",
+            aliases: &[],
+            usage: "gfx [13h|12h]",
+            summary: "Show a VGA graphics demo (320x200x256 or 640x480x16)",
+            handler: cmd_gfx,
+        },
+        Command {
+            name: "mode",
+            aliases: &[],
+            usage: "mode [80x25|80x50|90x60]",
+            summary: "Show or change the text mode",
+            handler: cmd_mode,
+        },
+    ]);
+}
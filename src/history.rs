@@ -0,0 +1,259 @@
+use crate::commands::{self, Command};
+use crate::io::{Io, Sink};
+use crate::file_system::{self, FileSystemError};
+
+pub const HISTORY_FILE: &str = "~/.history";
+const HISTORY_SIZE: usize = 32;
+const ENTRY_LEN: usize = 256;
+
+struct History {
+    entries: [[u8; ENTRY_LEN]; HISTORY_SIZE],
+    lens: [usize; HISTORY_SIZE],
+    start: usize,
+    count: usize,
+    total: usize,
+}
+
+static mut HISTORY: History = History {
+    entries: [[0; ENTRY_LEN]; HISTORY_SIZE],
+    lens: [0; HISTORY_SIZE],
+    start: 0,
+    count: 0,
+    total: 0,
+};
+
+fn history() -> &'static mut History {
+    unsafe { &mut *(&raw mut HISTORY) }
+}
+
+pub fn len() -> usize {
+    history().count
+}
+
+pub fn get(index: usize) -> Option<&'static str> {
+    let h = history();
+    if index >= h.count {
+        return None;
+    }
+    let slot = (h.start + index) % HISTORY_SIZE;
+    core::str::from_utf8(&h.entries[slot][..h.lens[slot]]).ok()
+}
+
+pub fn number_of(index: usize) -> usize {
+    let h = history();
+    h.total - h.count + index + 1
+}
+
+pub fn get_by_number(number: usize) -> Option<&'static str> {
+    let h = history();
+    let first = h.total - h.count + 1;
+    if number < first {
+        return None;
+    }
+    get(number - first)
+}
+
+pub fn last() -> Option<&'static str> {
+    let count = len();
+    if count == 0 { None } else { get(count - 1) }
+}
+
+pub fn push(line: &str) {
+    if append(line) && crate::settings::get_settings().save_history {
+        let _ = save();
+    }
+}
+
+fn append(line: &str) -> bool {
+    let line = line.trim();
+    if line.is_empty() || line.len() > ENTRY_LEN || last() == Some(line) {
+        return false;
+    }
+
+    let h = history();
+    let slot = if h.count < HISTORY_SIZE {
+        let slot = (h.start + h.count) % HISTORY_SIZE;
+        h.count += 1;
+        slot
+    } else {
+        let slot = h.start;
+        h.start = (h.start + 1) % HISTORY_SIZE;
+        slot
+    };
+    h.entries[slot][..line.len()].copy_from_slice(line.as_bytes());
+    h.lens[slot] = line.len();
+    h.total += 1;
+    true
+}
+
+pub fn clear() {
+    let h = history();
+    h.start = 0;
+    h.count = 0;
+}
+
+pub fn search(query: &str, before: usize) -> Option<usize> {
+    let mut index = before.min(len());
+    while index > 0 {
+        index -= 1;
+        if let Some(entry) = get(index) {
+            if entry.contains(query) {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+pub fn expand<'a>(input: &str, out: &'a mut [u8]) -> Result<Option<&'a str>, &'static str> {
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    let mut len = 0;
+    let mut expanded = false;
+
+    while i < bytes.len() {
+        let replacement = if bytes[i] == b'!' && i + 1 < bytes.len() {
+            if bytes[i + 1] == b'!' {
+                i += 2;
+                Some(last().ok_or("!!: event not found")?)
+            } else if bytes[i + 1].is_ascii_digit() {
+                let mut number = 0usize;
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    number = number.saturating_mul(10).saturating_add((bytes[i] - b'0') as usize);
+                    i += 1;
+                }
+                Some(get_by_number(number).ok_or("!n: event not found")?)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        match replacement {
+            Some(text) => {
+                if len + text.len() > out.len() {
+                    return Err("Expanded line too long");
+                }
+                out[len..len + text.len()].copy_from_slice(text.as_bytes());
+                len += text.len();
+                expanded = true;
+            }
+            None => {
+                if len >= out.len() {
+                    return Err("Expanded line too long");
+                }
+                out[len] = bytes[i];
+                len += 1;
+                i += 1;
+            }
+        }
+    }
+
+    if !expanded {
+        return Ok(None);
+    }
+    core::str::from_utf8(&out[..len]).map(Some).map_err(|_| "Invalid UTF-8 in history")
+}
+
+pub fn save() -> Result<(), FileSystemError> {
+    let mut data = [0u8; file_system::MAX_FILE_SIZE];
+    let mut len = 0;
+
+    let mut first = 0;
+    let mut total = 0;
+    for index in (0..self::len()).rev() {
+        let entry_len = get(index).map(|e| e.len() + 1).unwrap_or(0);
+        if total + entry_len > data.len() {
+            first = index + 1;
+            break;
+        }
+        total += entry_len;
+    }
+
+    for index in first..self::len() {
+        if let Some(entry) = get(index) {
+            data[len..len + entry.len()].copy_from_slice(entry.as_bytes());
+            len += entry.len();
+            data[len] = b'\n';
+            len += 1;
+        }
+    }
+
+    file_system::with_fs_mut(|fs| fs.write_file(HISTORY_FILE, &data[..len]))
+}
+
+pub fn load() -> Result<usize, FileSystemError> {
+    let mut data = [0u8; file_system::MAX_FILE_SIZE];
+    let len = file_system::with_fs(|fs| {
+        fs.read_file(HISTORY_FILE).map(|file| {
+            data[..file.len()].copy_from_slice(file);
+            file.len()
+        })
+    })?;
+
+    let text = core::str::from_utf8(&data[..len]).map_err(|_| FileSystemError::ReadError)?;
+    let mut loaded = 0;
+    for line in text.lines() {
+        if append(line) {
+            loaded += 1;
+        }
+    }
+    Ok(loaded)
+}
+
+fn cmd_history(io: &mut Io, argv: &[&str]) -> i32 {
+    match argv.get(1).copied() {
+        None => {
+            for index in 0..len() {
+                if let Some(entry) = get(index) {
+                    writeln!(io, "{:>5}  {}", number_of(index), entry);
+                }
+            }
+            0
+        }
+        Some("-c") => {
+            clear();
+            0
+        }
+        Some("-w") => match save() {
+            Ok(_) => {
+                io.write_string("History saved to ");
+                io.write_string(HISTORY_FILE);
+                io.write_string("\n");
+                0
+            }
+            Err(_) => {
+                io.write_string("Error: Could not save history.\n");
+                1
+            }
+        },
+        Some("-r") => match load() {
+            Ok(count) => {
+                write!(io, "Loaded {} entries from ", count);
+                io.write_string(HISTORY_FILE);
+                io.write_string("\n");
+                0
+            }
+            Err(_) => {
+                io.write_string("Error: No saved history found.\n");
+                1
+            }
+        },
+        Some(_) => {
+            io.write_string("Usage: history [-c | -w | -r]\n");
+            2
+        }
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[Command {
+        name: "history",
+        aliases: &[],
+        usage: "history [-c | -w | -r]",
+        summary: "Show, clear, save or reload the command history",
+        handler: cmd_history,
+    }]);
+}
@@ -0,0 +1,80 @@
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use crate::{port, statusbar};
+
+pub use x86_64::instructions::interrupts::without_interrupts;
+
+const PIC1_COMMAND: u16 = 0x20;
+const PIC1_DATA: u16 = 0x21;
+const PIC2_COMMAND: u16 = 0xA0;
+const PIC2_DATA: u16 = 0xA1;
+const PIC_INIT: u8 = 0x11;
+const PIC_8086_MODE: u8 = 0x01;
+const PIC_EOI: u8 = 0x20;
+
+// IRQs 0-15 are moved past the CPU exceptions.
+const PIC1_OFFSET: u8 = 32;
+const PIC2_OFFSET: u8 = PIC1_OFFSET + 8;
+const TIMER_VECTOR: u8 = PIC1_OFFSET;
+// IRQ 7 is also what the PIC reports when an interrupt goes away before it is acknowledged.
+const SPURIOUS_VECTOR: u8 = PIC1_OFFSET + 7;
+
+const PIT_CHANNEL0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+// Channel 0, low then high byte, square wave.
+const PIT_SQUARE_WAVE: u8 = 0x36;
+const PIT_FREQUENCY: u32 = 1_193_182;
+pub const TICKS_PER_SECOND: u32 = 100;
+
+static mut IDT: Option<InterruptDescriptorTable> = None;
+static mut TICKS: u64 = 0;
+
+extern "x86-interrupt" fn timer_handler(_frame: InterruptStackFrame) {
+    unsafe { TICKS += 1 };
+    statusbar::tick();
+    port::outb(PIC1_COMMAND, PIC_EOI);
+}
+
+extern "x86-interrupt" fn spurious_handler(_frame: InterruptStackFrame) {}
+
+/// Remaps the PICs, with every line but the timer masked; keyboard and network are polled.
+fn init_pics() {
+    port::outb(PIC1_COMMAND, PIC_INIT);
+    port::outb(PIC2_COMMAND, PIC_INIT);
+    port::outb(PIC1_DATA, PIC1_OFFSET);
+    port::outb(PIC2_DATA, PIC2_OFFSET);
+    // The slave PIC hangs off IRQ 2.
+    port::outb(PIC1_DATA, 1 << 2);
+    port::outb(PIC2_DATA, 2);
+    port::outb(PIC1_DATA, PIC_8086_MODE);
+    port::outb(PIC2_DATA, PIC_8086_MODE);
+    port::outb(PIC1_DATA, !1);
+    port::outb(PIC2_DATA, 0xFF);
+}
+
+fn init_pit() {
+    let divisor = (PIT_FREQUENCY / TICKS_PER_SECOND) as u16;
+    port::outb(PIT_COMMAND, PIT_SQUARE_WAVE);
+    port::outb(PIT_CHANNEL0, divisor as u8);
+    port::outb(PIT_CHANNEL0, (divisor >> 8) as u8);
+}
+
+/// Installs the IDT and starts the timer interrupt.
+pub fn init() {
+    let mut idt = InterruptDescriptorTable::new();
+    idt[TIMER_VECTOR].set_handler_fn(timer_handler);
+    idt[SPURIOUS_VECTOR].set_handler_fn(spurious_handler);
+    let idt = unsafe {
+        *(&raw mut IDT) = Some(idt);
+        (*(&raw const IDT)).as_ref().unwrap()
+    };
+    idt.load();
+
+    init_pics();
+    init_pit();
+    x86_64::instructions::interrupts::enable();
+}
+
+/// Timer interrupts since `init`.
+pub fn ticks() -> u64 {
+    unsafe { TICKS }
+}
@@ -0,0 +1,205 @@
+use core::fmt;
+
+use crate::vga_buffer::{ColorCode, Writer};
+
+pub const PIPE_SIZE: usize = 4096;
+
+pub trait Sink {
+    fn write_byte(&mut self, byte: u8);
+
+    fn write_string(&mut self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+    }
+
+    fn set_color(&mut self, _color: ColorCode) {}
+
+    fn column(&self) -> usize;
+
+    /// Lets `write!` format straight into any sink. Sinks cannot fail, so
+    /// unlike `fmt::Write::write_fmt` this returns nothing.
+    fn write_fmt(&mut self, args: fmt::Arguments) {
+        struct Adapter<'a, S: Sink + ?Sized>(&'a mut S);
+
+        impl<S: Sink + ?Sized> fmt::Write for Adapter<'_, S> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0.write_string(s);
+                Ok(())
+            }
+        }
+
+        let _ = fmt::write(&mut Adapter(self), args);
+    }
+}
+
+impl Sink for Writer {
+    fn write_byte(&mut self, byte: u8) {
+        Writer::write_byte(self, byte);
+    }
+
+    fn write_string(&mut self, s: &str) {
+        Writer::write_string(self, s);
+    }
+
+    fn set_color(&mut self, color: ColorCode) {
+        self.color_code = color;
+    }
+
+    fn column(&self) -> usize {
+        self.column_position
+    }
+}
+
+pub struct PipeBuffer {
+    data: [u8; PIPE_SIZE],
+    len: usize,
+    column: usize,
+    overflowed: bool,
+}
+
+impl PipeBuffer {
+    pub const fn new() -> Self {
+        PipeBuffer { data: [0; PIPE_SIZE], len: 0, column: 0, overflowed: false }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.column = 0;
+        self.overflowed = false;
+    }
+
+    pub fn fill(&mut self, data: &[u8]) {
+        self.clear();
+        for &byte in data {
+            self.write_byte(byte);
+        }
+    }
+}
+
+impl Sink for PipeBuffer {
+    fn write_byte(&mut self, byte: u8) {
+        if self.len >= PIPE_SIZE {
+            self.overflowed = true;
+            return;
+        }
+        self.data[self.len] = byte;
+        self.len += 1;
+        self.column = if byte == b'\n' { 0 } else { self.column + 1 };
+    }
+
+    fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// A short line of text built with `write!`; whatever doesn't fit is dropped.
+pub struct TextBuffer<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TextBuffer<N> {
+    pub const fn new() -> Self {
+        TextBuffer { data: [0; N], len: 0 }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl<const N: usize> Sink for TextBuffer<N> {
+    fn write_byte(&mut self, byte: u8) {
+        if self.len < N {
+            self.data[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.len
+    }
+}
+
+pub struct Stdin<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Stdin<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Stdin { data, pos: 0 }
+    }
+
+    pub fn read_all(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+
+    pub fn read_line(&mut self) -> Option<&'a [u8]> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.pos += (end + 1).min(rest.len());
+        Some(&rest[..end])
+    }
+}
+
+pub struct Io<'a> {
+    pub console: &'a mut Writer,
+    pub stdin: Option<Stdin<'a>>,
+    sink: Option<&'a mut dyn Sink>,
+}
+
+impl<'a> Io<'a> {
+    pub fn new(console: &'a mut Writer, stdin: Option<&'a [u8]>, sink: Option<&'a mut dyn Sink>) -> Self {
+        Io { console, stdin: stdin.map(Stdin::new), sink }
+    }
+
+    pub fn parts(&mut self) -> (&mut Writer, Option<&mut dyn Sink>) {
+        match &mut self.sink {
+            Some(sink) => (&mut *self.console, Some(&mut **sink)),
+            None => (&mut *self.console, None),
+        }
+    }
+
+    fn out(&mut self) -> &mut dyn Sink {
+        match &mut self.sink {
+            Some(sink) => &mut **sink,
+            None => self.console,
+        }
+    }
+}
+
+impl Sink for Io<'_> {
+    fn write_byte(&mut self, byte: u8) {
+        self.out().write_byte(byte);
+    }
+
+    fn write_string(&mut self, s: &str) {
+        self.out().write_string(s);
+    }
+
+    fn set_color(&mut self, color: ColorCode) {
+        self.out().set_color(color);
+    }
+
+    fn column(&self) -> usize {
+        match &self.sink {
+            Some(sink) => sink.column(),
+            None => self.console.column_position,
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use crate::keymap::{self, KeySymbol};
+use crate::{net, settings, statusbar};
+
+const EXTENDED_PREFIX: u8 = 0xE0;
+const PAUSE_PREFIX: u8 = 0xE1;
+// Pause sends E1 1D 45 E1 9D C5 and nothing when released.
+const PAUSE_LENGTH: u8 = 5;
+const RELEASE: u8 = 0x80;
+const ACK: u8 = 0xFA;
+const RESEND: u8 = 0xFE;
+const OVERRUN: u8 = 0xFF;
+
+// Accent, plain letters and the same letters with the accent, in matching order.
+const COMPOSE: &[(char, &str, &str)] = &[
+    ('´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+];
+
+/// The character a dead `accent` followed by `c` makes. Space, or the accent again, types the
+/// accent itself.
+fn compose(accent: char, c: char) -> Option<char> {
+    if c == ' ' || c == accent {
+        return Some(accent);
+    }
+    let (_, plain, accented) = COMPOSE.iter().find(|(a, _, _)| *a == accent)?;
+    let index = plain.chars().position(|p| p == c)?;
+    accented.chars().nth(index)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A key that types a character in the current layout, with Shift and Caps Lock applied.
+    Char(char),
+    /// A dead key: its accent is combined with the next character typed.
+    Dead(char),
+    Escape,
+    Enter,
+    Backspace,
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    F(u8),
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    Alt,
+    AltGr,
+    LeftGui,
+    RightGui,
+    Menu,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    PrintScreen,
+    Pause,
+    /// A key with no meaning in the current layout, by scancode (0x80 set for extended keys).
+    Other(u8),
+}
+
+impl Key {
+    pub fn is_modifier(self) -> bool {
+        matches!(
+            self,
+            Key::LeftShift
+                | Key::RightShift
+                | Key::LeftCtrl
+                | Key::RightCtrl
+                | Key::Alt
+                | Key::AltGr
+                | Key::LeftGui
+                | Key::RightGui
+                | Key::CapsLock
+                | Key::NumLock
+                | Key::ScrollLock
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub alt: bool,
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    /// Modifier state after this event was applied.
+    pub modifiers: Modifiers,
+    pub pressed: bool,
+}
+
+/// Turns scancode set 1 bytes into key events, keeping track of the modifiers.
+pub struct Decoder {
+    modifiers: Modifiers,
+    extended: bool,
+    pause_remaining: u8,
+    // Held lock keys, so typematic repeats don't toggle them again.
+    caps_held: bool,
+    num_held: bool,
+    dead: Option<char>,
+    // A character that didn't combine with the dead key before it, typed after the accent.
+    queued: Option<KeyEvent>,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder {
+            modifiers: Modifiers {
+                left_shift: false,
+                right_shift: false,
+                left_ctrl: false,
+                right_ctrl: false,
+                alt: false,
+                alt_gr: false,
+                caps_lock: false,
+                num_lock: false,
+            },
+            extended: false,
+            pause_remaining: 0,
+            caps_held: false,
+            num_held: false,
+            dead: None,
+            queued: None,
+        }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<KeyEvent> {
+        // Caps Lock lives in the settings, where the menu and status bar read it.
+        self.modifiers.caps_lock = settings::get_caps_lock_state();
+
+        if self.pause_remaining > 0 {
+            self.pause_remaining -= 1;
+            return (self.pause_remaining == 0).then_some(KeyEvent { key: Key::Pause, modifiers: self.modifiers, pressed: true });
+        }
+        match byte {
+            EXTENDED_PREFIX => {
+                self.extended = true;
+                return None;
+            }
+            PAUSE_PREFIX => {
+                self.pause_remaining = PAUSE_LENGTH;
+                return None;
+            }
+            0x00 | ACK | RESEND | OVERRUN => return None,
+            _ => {}
+        }
+
+        let extended = core::mem::take(&mut self.extended);
+        let pressed = byte & RELEASE == 0;
+        let code = byte & !RELEASE;
+        // Extended shifts are fake ones the keyboard wraps around Print Screen and the
+        // navigation keys; the real shift state is already known.
+        if extended && (code == 0x2A || code == 0x36) {
+            return None;
+        }
+
+        let key = if extended { self.extended_key(code) } else { self.key(code) };
+        let modifiers = &mut self.modifiers;
+        match key {
+            Key::LeftShift => modifiers.left_shift = pressed,
+            Key::RightShift => modifiers.right_shift = pressed,
+            Key::LeftCtrl => modifiers.left_ctrl = pressed,
+            Key::RightCtrl => modifiers.right_ctrl = pressed,
+            Key::Alt => modifiers.alt = pressed,
+            Key::AltGr => modifiers.alt_gr = pressed,
+            Key::CapsLock => {
+                if pressed && !self.caps_held {
+                    settings::toggle_caps_lock();
+                    modifiers.caps_lock = !modifiers.caps_lock;
+                    statusbar::draw();
+                }
+                self.caps_held = pressed;
+            }
+            Key::NumLock => {
+                if pressed && !self.num_held {
+                    modifiers.num_lock = !modifiers.num_lock;
+                }
+                self.num_held = pressed;
+            }
+            _ => {}
+        }
+        let key = if pressed { self.compose(key) } else { key };
+        Some(KeyEvent { key, modifiers: self.modifiers, pressed })
+    }
+
+    /// Applies a pending dead key to a press.
+    fn compose(&mut self, key: Key) -> Key {
+        if key.is_modifier() {
+            return key;
+        }
+        let Some(accent) = self.dead.take() else {
+            if let Key::Dead(accent) = key {
+                self.dead = Some(accent);
+            }
+            return key;
+        };
+        let c = match key {
+            Key::Char(c) => c,
+            Key::Dead(c) if c == accent => c,
+            Key::Dead(c) => {
+                self.dead = Some(c);
+                return Key::Char(accent);
+            }
+            _ => return key,
+        };
+        match compose(accent, c) {
+            Some(composed) => Key::Char(composed),
+            None => {
+                self.queued = Some(KeyEvent { key: Key::Char(c), modifiers: self.modifiers, pressed: true });
+                Key::Char(accent)
+            }
+        }
+    }
+
+    /// A key press left over from an earlier byte.
+    pub fn take_queued(&mut self) -> Option<KeyEvent> {
+        self.queued.take()
+    }
+
+    fn key(&self, code: u8) -> Key {
+        match code {
+            0x01 => Key::Escape,
+            0x0E => Key::Backspace,
+            0x0F => Key::Tab,
+            0x1C => Key::Enter,
+            0x1D => Key::LeftCtrl,
+            0x2A => Key::LeftShift,
+            0x36 => Key::RightShift,
+            0x37 => Key::Char('*'),
+            0x38 => Key::Alt,
+            0x3A => Key::CapsLock,
+            0x3B..=0x44 => Key::F(code - 0x3A),
+            0x45 => Key::NumLock,
+            0x46 => Key::ScrollLock,
+            0x4A => Key::Char('-'),
+            0x4E => Key::Char('+'),
+            0x47..=0x53 => self.keypad_key(code),
+            0x57 => Key::F(11),
+            0x58 => Key::F(12),
+            _ => match keymap::lookup(code, self.modifiers.shift(), self.modifiers.alt_gr) {
+                Some(KeySymbol::Char(c)) => Key::Char(c),
+                Some(KeySymbol::Dead(accent)) => Key::Dead(accent),
+                None => Key::Other(code),
+            },
+        }
+    }
+
+    /// The keypad types digits with Num Lock on (and Shift up), and moves the cursor otherwise.
+    fn keypad_key(&self, code: u8) -> Key {
+        if self.modifiers.num_lock && !self.modifiers.shift() {
+            return match code {
+                0x47 => Key::Char('7'),
+                0x48 => Key::Char('8'),
+                0x49 => Key::Char('9'),
+                0x4B => Key::Char('4'),
+                0x4C => Key::Char('5'),
+                0x4D => Key::Char('6'),
+                0x4F => Key::Char('1'),
+                0x50 => Key::Char('2'),
+                0x51 => Key::Char('3'),
+                0x52 => Key::Char('0'),
+                0x53 => Key::Char('.'),
+                _ => Key::Other(code),
+            };
+        }
+        match code {
+            0x4C => Key::Other(code),
+            _ => self.extended_key(code),
+        }
+    }
+
+    fn extended_key(&self, code: u8) -> Key {
+        match code {
+            0x1C => Key::Enter,
+            0x1D => Key::RightCtrl,
+            0x35 => Key::Char('/'),
+            0x37 => Key::PrintScreen,
+            0x38 => Key::AltGr,
+            0x47 => Key::Home,
+            0x48 => Key::Up,
+            0x49 => Key::PageUp,
+            0x4B => Key::Left,
+            0x4D => Key::Right,
+            0x4F => Key::End,
+            0x50 => Key::Down,
+            0x51 => Key::PageDown,
+            0x52 => Key::Insert,
+            0x53 => Key::Delete,
+            0x5B => Key::LeftGui,
+            0x5C => Key::RightGui,
+            0x5D => Key::Menu,
+            _ => Key::Other(code | RELEASE),
+        }
+    }
+}
+
+static mut DECODER: Decoder = Decoder::new();
+
+fn decoder() -> &'static mut Decoder {
+    unsafe { &mut *(&raw mut DECODER) }
+}
+
+/// Waits for the next byte from the keyboard controller, polling the network meanwhile.
+pub fn read_scancode() -> u8 {
+    loop {
+        let mut status: u8;
+        unsafe { core::arch::asm!("in al, 0x64", out("al") status, options(nomem, nostack, preserves_flags)); }
+        if status & 1 != 0 {
+            let mut sc: u8;
+            unsafe { core::arch::asm!("in al, 0x60", out("al") sc, options(nomem, nostack, preserves_flags)); }
+            return sc;
+        }
+        net::poll();
+    }
+}
+
+/// Next press or release.
+pub fn read_event() -> KeyEvent {
+    if let Some(event) = decoder().take_queued() {
+        return event;
+    }
+    loop {
+        if let Some(event) = decoder().feed(read_scancode()) {
+            return event;
+        }
+    }
+}
+
+/// Next key press. Releases still update the modifiers on the way.
+pub fn read_key() -> KeyEvent {
+    loop {
+        let event = read_event();
+        if event.pressed {
+            return event;
+        }
+    }
+}
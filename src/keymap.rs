@@ -0,0 +1,118 @@
+use crate::commands::{self, Command};
+use crate::file_system;
+use crate::io::{Io, Sink};
+use crate::kmap::{self, Keymap};
+use crate::settings::{self, KeyboardLayout};
+
+pub use crate::kmap::KeySymbol;
+
+const BUILTIN: [(KeyboardLayout, &str, &str); 6] = [
+    (KeyboardLayout::Us, "us.kmap", include_str!("../keymaps/us.kmap")),
+    (KeyboardLayout::Uk, "uk.kmap", include_str!("../keymaps/uk.kmap")),
+    (KeyboardLayout::German, "de.kmap", include_str!("../keymaps/de.kmap")),
+    (KeyboardLayout::French, "fr.kmap", include_str!("../keymaps/fr.kmap")),
+    (KeyboardLayout::Spanish, "es.kmap", include_str!("../keymaps/es.kmap")),
+    (KeyboardLayout::Dvorak, "dvorak.kmap", include_str!("../keymaps/dvorak.kmap")),
+];
+
+static mut ACTIVE: Keymap = Keymap::new();
+// Built-in layout currently parsed into ACTIVE, if any.
+static mut ACTIVE_LAYOUT: Option<KeyboardLayout> = None;
+
+/// The keymap for the layout chosen in the settings, parsing a built-in one on first use.
+fn active() -> &'static Keymap {
+    let keymap = unsafe { &mut *(&raw mut ACTIVE) };
+    let layout = settings::get_settings().keyboard_layout;
+    if layout != KeyboardLayout::Custom && unsafe { ACTIVE_LAYOUT } != Some(layout) {
+        if let Some((_, _, text)) = BUILTIN.iter().find(|(builtin, _, _)| *builtin == layout) {
+            // check_builtin reported any error at boot; the lines before it still work.
+            let _ = kmap::parse(text, keymap);
+        }
+        unsafe { ACTIVE_LAYOUT = Some(layout) };
+    }
+    keymap
+}
+
+/// Reports built-in keymaps with errors, which would otherwise only show up as dead keys.
+pub fn check_builtin(out: &mut dyn Sink) {
+    let mut keymap = Keymap::new();
+    for (_, file, text) in BUILTIN.iter() {
+        if let Err((line, e)) = kmap::parse(text, &mut keymap) {
+            writeln!(out, "keymap: built-in {}: line {}: {}", file, line, e);
+        }
+    }
+}
+
+pub fn name() -> &'static str {
+    active().name()
+}
+
+/// What a key types with the given modifiers and the current Caps Lock state.
+pub fn lookup(sc: u8, shift: bool, altgr: bool) -> Option<KeySymbol> {
+    active().lookup(sc, shift, altgr, settings::get_caps_lock_state())
+}
+
+pub fn scancode_to_char(sc: u8, shift: bool) -> Option<char> {
+    match lookup(sc, shift, false) {
+        Some(KeySymbol::Char(c)) => Some(c),
+        _ => None,
+    }
+}
+
+/// Loads a keymap file and makes it the current layout.
+pub fn load(path: &str) -> Result<(), (usize, &'static str)> {
+    let mut keymap = Keymap::new();
+    file_system::with_fs(|fs| {
+        let data = fs.read_file(path).map_err(|_| (0, "no such file"))?;
+        let text = core::str::from_utf8(data).map_err(|_| (0, "not UTF-8 text"))?;
+        kmap::parse(text, &mut keymap)
+    })?;
+    if keymap.name().is_empty() {
+        keymap.set_name(path.rsplit('/').next().unwrap_or(path));
+    }
+
+    unsafe {
+        ACTIVE = keymap;
+        ACTIVE_LAYOUT = None;
+    }
+    let mut settings = settings::get_settings();
+    settings.keyboard_layout = KeyboardLayout::Custom;
+    settings::set_settings(settings);
+    Ok(())
+}
+
+fn cmd_loadkeys(io: &mut Io, argv: &[&str]) -> i32 {
+    match argv.get(1).copied() {
+        None => {
+            writeln!(io, "{}", name());
+            0
+        }
+        Some("-d") => {
+            let mut settings = settings::get_settings();
+            settings.keyboard_layout = KeyboardLayout::Us;
+            settings::set_settings(settings);
+            0
+        }
+        Some(path) => match load(path) {
+            Ok(()) => 0,
+            Err((0, e)) => {
+                writeln!(io, "loadkeys: {}: {}", path, e);
+                1
+            }
+            Err((line, e)) => {
+                writeln!(io, "loadkeys: {}: line {}: {}", path, line, e);
+                1
+            }
+        },
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[Command {
+        name: "loadkeys",
+        aliases: &[],
+        usage: "loadkeys [file.kmap | -d]",
+        summary: "Load a keymap file (-d restores the US layout)",
+        handler: cmd_loadkeys,
+    }]);
+}
@@ -0,0 +1,130 @@
+//! The keymap file format. This module only uses `core`, so `tools/map2kmap` builds it into its
+//! tests to check the built-in keymaps.
+
+pub const KEY_COUNT: usize = 128;
+const NAME_LEN: usize = 32;
+
+/// What a key types: a character, or a dead accent that combines with the next key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySymbol {
+    Char(char),
+    Dead(char),
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    plain: Option<KeySymbol>,
+    shift: Option<KeySymbol>,
+    altgr: Option<KeySymbol>,
+    /// Caps Lock works like Shift on this key.
+    caps: bool,
+}
+
+const EMPTY: Entry = Entry { plain: None, shift: None, altgr: None, caps: false };
+
+pub struct Keymap {
+    entries: [Entry; KEY_COUNT],
+    name: [u8; NAME_LEN],
+    name_len: usize,
+}
+
+impl Keymap {
+    pub const fn new() -> Self {
+        Keymap { entries: [EMPTY; KEY_COUNT], name: [0; NAME_LEN], name_len: 0 }
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// Sets the name, cut to fit.
+    pub fn set_name(&mut self, name: &str) {
+        let mut len = name.len().min(NAME_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+        self.name_len = len;
+    }
+
+    pub fn lookup(&self, sc: u8, shift: bool, altgr: bool, caps_lock: bool) -> Option<KeySymbol> {
+        let entry = self.entries.get(sc as usize)?;
+        if altgr {
+            entry.altgr
+        } else if shift ^ (caps_lock && entry.caps) {
+            entry.shift
+        } else {
+            entry.plain
+        }
+    }
+}
+
+fn parse_char(token: &str) -> Option<char> {
+    if let Some(hex) = token.strip_prefix("U+") {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    let mut chars = token.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+fn parse_symbol(token: &str) -> Result<Option<KeySymbol>, &'static str> {
+    if token == "none" {
+        return Ok(None);
+    }
+    if let Some(accent) = token.strip_prefix("dead:") {
+        return parse_char(accent).map(|c| Some(KeySymbol::Dead(c))).ok_or("bad dead key");
+    }
+    parse_char(token).map(|c| Some(KeySymbol::Char(c))).ok_or("bad symbol")
+}
+
+fn parse_scancode(token: &str) -> Option<usize> {
+    let code = match token.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None => token.parse().ok()?,
+    };
+    (code < KEY_COUNT).then_some(code)
+}
+
+fn parse_line(keymap: &mut Keymap, line: &str) -> Result<(), &'static str> {
+    if let Some(name) = line.strip_prefix("name ") {
+        keymap.set_name(name.trim());
+        return Ok(());
+    }
+
+    let mut tokens = line.split_whitespace();
+    let code = tokens.next().and_then(parse_scancode).ok_or("bad scancode")?;
+    let mut entry = EMPTY;
+    entry.plain = parse_symbol(tokens.next().ok_or("missing symbols")?)?;
+    entry.shift = match tokens.next() {
+        Some(token) => parse_symbol(token)?,
+        None => entry.plain,
+    };
+    entry.altgr = match tokens.next() {
+        Some(token) => parse_symbol(token)?,
+        None => None,
+    };
+    match tokens.next() {
+        Some("caps") => entry.caps = true,
+        Some(_) => return Err("expected caps"),
+        None => {}
+    }
+    if tokens.next().is_some() {
+        return Err("too many columns");
+    }
+    keymap.entries[code] = entry;
+    Ok(())
+}
+
+/// Parses a keymap file. Errors carry the line number they were found on.
+pub fn parse(text: &str, keymap: &mut Keymap) -> Result<(), (usize, &'static str)> {
+    *keymap = Keymap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        parse_line(keymap, line).map_err(|e| (index + 1, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,459 @@
+use crate::console::{self, CONSOLE_COUNT};
+use crate::keyboard::{self, Key, KeyEvent};
+use crate::vga_buffer;
+use crate::{completion, cp437, history};
+
+const DRAFT_LEN: usize = 256;
+const QUERY_LEN: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Suspended {
+    line: [u8; DRAFT_LEN],
+    len: usize,
+    cursor: usize,
+    start_row: usize,
+    start_col: usize,
+    used: bool,
+}
+
+const NOT_SUSPENDED: Suspended = Suspended {
+    line: [0; DRAFT_LEN],
+    len: 0,
+    cursor: 0,
+    start_row: 0,
+    start_col: 0,
+    used: false,
+};
+
+static mut SUSPENDED: [Suspended; CONSOLE_COUNT] = [NOT_SUSPENDED; CONSOLE_COUNT];
+
+fn suspended(index: usize) -> &'static mut Suspended {
+    unsafe { &mut *(&raw mut SUSPENDED[index]) }
+}
+
+struct LineEditor<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    cursor: usize,
+    start_row: usize,
+    start_col: usize,
+    shown: usize,
+}
+
+impl<'a> LineEditor<'a> {
+    fn new(writer: &vga_buffer::Writer, buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            len: 0,
+            cursor: 0,
+            start_row: writer.row_position,
+            start_col: writer.column_position,
+            shown: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.buffer.len().saturating_sub(1)
+    }
+
+    fn ensure_rows(&mut self, writer: &mut vga_buffer::Writer, cells: usize) {
+        let end_row = self.start_row + (self.start_col + cells) / vga_buffer::width();
+        if end_row >= vga_buffer::height() {
+            let scroll = end_row - (vga_buffer::height() - 1);
+            for _ in 0..scroll {
+                writer.scroll_up();
+            }
+            self.start_row = self.start_row.saturating_sub(scroll);
+        }
+    }
+
+    fn move_to(&self, writer: &mut vga_buffer::Writer, index: usize) {
+        let cell = self.start_col + index;
+        let width = vga_buffer::width();
+        writer.set_cursor_position(self.start_row + cell / width, cell % width);
+    }
+
+    fn render_text(&mut self, writer: &mut vga_buffer::Writer, text: &[u8], from: usize, cursor: usize) {
+        let cells = text.len().max(self.shown).max(cursor);
+        self.ensure_rows(writer, cells);
+        self.move_to(writer, from);
+        for &glyph in &text[from.min(text.len())..] {
+            writer.write_glyph(glyph);
+        }
+        for _ in text.len().max(from)..self.shown {
+            writer.write_byte(b' ');
+        }
+        self.shown = text.len();
+        self.move_to(writer, cursor);
+    }
+
+    fn render(&mut self, writer: &mut vga_buffer::Writer, from: usize) {
+        let len = self.len;
+        let cursor = self.cursor;
+        let mut text = [0u8; 1024];
+        let n = len.min(text.len());
+        text[..n].copy_from_slice(&self.buffer[..n]);
+        self.render_text(writer, &text[..n], from, cursor);
+    }
+
+    fn set_text(&mut self, writer: &mut vga_buffer::Writer, text: &str) {
+        let mut glyphs = [0u8; 1024];
+        let n = cp437::decode_utf8(text.as_bytes(), &mut glyphs);
+        self.set_content(writer, &glyphs[..n]);
+    }
+
+    fn set_content(&mut self, writer: &mut vga_buffer::Writer, content: &[u8]) {
+        let n = content.len().min(self.capacity());
+        self.buffer[..n].copy_from_slice(&content[..n]);
+        self.len = n;
+        self.cursor = n;
+        self.render(writer, 0);
+    }
+
+    fn insert(&mut self, writer: &mut vga_buffer::Writer, byte: u8) {
+        if self.len >= self.capacity() {
+            return;
+        }
+        self.buffer.copy_within(self.cursor..self.len, self.cursor + 1);
+        self.buffer[self.cursor] = byte;
+        self.len += 1;
+        self.cursor += 1;
+        self.render(writer, self.cursor - 1);
+    }
+
+    fn delete_range(&mut self, writer: &mut vga_buffer::Writer, from: usize, to: usize) {
+        if from >= to {
+            return;
+        }
+        self.buffer.copy_within(to..self.len, from);
+        self.len -= to - from;
+        self.cursor = from;
+        self.render(writer, from);
+    }
+
+    fn move_cursor(&mut self, writer: &mut vga_buffer::Writer, index: usize) {
+        self.cursor = index.min(self.len);
+        self.move_to(writer, self.cursor);
+    }
+
+    fn suspend(&self, index: usize) {
+        let saved = suspended(index);
+        let n = self.len.min(DRAFT_LEN);
+        saved.line[..n].copy_from_slice(&self.buffer[..n]);
+        saved.len = n;
+        saved.cursor = self.cursor.min(n);
+        saved.start_row = self.start_row;
+        saved.start_col = self.start_col;
+        saved.used = true;
+    }
+
+    fn resume(&mut self, writer: &mut vga_buffer::Writer, saved: &Suspended) {
+        let n = saved.len.min(self.capacity());
+        self.buffer[..n].copy_from_slice(&saved.line[..n]);
+        self.len = n;
+        // The text mode may have shrunk since the line was suspended.
+        self.start_row = saved.start_row.min(vga_buffer::height() - 1);
+        self.start_col = saved.start_col;
+        self.shown = n;
+        self.move_cursor(writer, saved.cursor);
+    }
+
+    fn word_start(&self) -> usize {
+        let mut index = self.cursor;
+        while index > 0 && self.buffer[index - 1] == b' ' {
+            index -= 1;
+        }
+        while index > 0 && self.buffer[index - 1] != b' ' {
+            index -= 1;
+        }
+        index
+    }
+
+    fn finish(&mut self, writer: &mut vga_buffer::Writer) -> usize {
+        self.move_to(writer, self.len);
+        writer.write_byte(b'\n');
+        let mut glyphs = [0u8; 1024];
+        let n = self.len.min(glyphs.len());
+        glyphs[..n].copy_from_slice(&self.buffer[..n]);
+        let capacity = self.capacity();
+        let len = cp437::encode_utf8(&glyphs[..n], &mut self.buffer[..capacity]);
+        self.buffer[len] = 0;
+        len
+    }
+}
+
+fn print_prompt(writer: &mut vga_buffer::Writer, prompt: &str) {
+    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black);
+    writer.write_string(prompt);
+    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+}
+
+fn show_candidates(writer: &mut vga_buffer::Writer, editor: &mut LineEditor, prompt: &str, candidates: &completion::Candidates) {
+    const COLUMN_WIDTH: usize = 20;
+
+    editor.move_to(writer, editor.len);
+    writer.write_byte(b'\n');
+    for i in 0..candidates.len() {
+        let (name, is_dir) = candidates.get(i);
+        let width = name.len() + is_dir as usize;
+        if writer.column_position > 0 && writer.column_position + width > vga_buffer::width() {
+            writer.write_byte(b'\n');
+        }
+        if is_dir {
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black);
+        }
+        for &glyph in name {
+            writer.write_glyph(glyph);
+        }
+        if is_dir {
+            writer.write_byte(b'/');
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        }
+        let padding = COLUMN_WIDTH - width % COLUMN_WIDTH;
+        for _ in 0..padding {
+            if writer.column_position >= vga_buffer::width() {
+                break;
+            }
+            writer.write_byte(b' ');
+        }
+    }
+    writer.write_byte(b'\n');
+
+    print_prompt(writer, prompt);
+    editor.start_row = writer.row_position;
+    editor.start_col = writer.column_position;
+    editor.shown = 0;
+    editor.render(writer, 0);
+}
+
+fn complete(writer: &mut vga_buffer::Writer, editor: &mut LineEditor, prompt: &str, list: bool) {
+    let (start, candidates) = completion::complete(&editor.buffer[..editor.len], editor.cursor);
+    let typed = editor.cursor - start;
+
+    match candidates.len() {
+        0 => {}
+        1 => {
+            let (name, is_dir) = candidates.get(0);
+            for &byte in &name[typed.min(name.len())..] {
+                editor.insert(writer, byte);
+            }
+            editor.insert(writer, if is_dir { b'/' } else { b' ' });
+        }
+        _ => {
+            let common = candidates.common_prefix_len();
+            if common > typed {
+                let (name, _) = candidates.get(0);
+                for &byte in &name[typed..common] {
+                    editor.insert(writer, byte);
+                }
+            } else if list {
+                show_candidates(writer, editor, prompt, &candidates);
+            }
+        }
+    }
+}
+
+fn search_display(query: &[u8], found: Option<usize>, out: &mut [u8]) -> usize {
+    let found_text = found.and_then(history::get).unwrap_or("");
+    let prefix: &[u8] = if found.is_some() || query.is_empty() {
+        b"(reverse-i-search)`"
+    } else {
+        b"(failed reverse-i-search)`"
+    };
+    let mut len = 0;
+    for part in [prefix, query, b"': ", found_text.as_bytes()] {
+        len += cp437::decode_utf8(part, &mut out[len..]);
+    }
+    len
+}
+
+pub fn read_line<'a>(writer: &mut vga_buffer::Writer, buffer: &'a mut [u8]) -> &'a str {
+    let editor = LineEditor::new(writer, buffer);
+    edit_line(writer, editor, "", false).unwrap_or("")
+}
+
+/// Returns `None` when the user switched to another console; the
+/// unfinished line is kept and restored when this console is active again.
+pub fn read_command<'a>(writer: &mut vga_buffer::Writer, prompt: &str, buffer: &'a mut [u8]) -> Option<&'a str> {
+    let saved = core::mem::replace(suspended(console::active()), NOT_SUSPENDED);
+    if !saved.used {
+        print_prompt(writer, prompt);
+    }
+    let mut editor = LineEditor::new(writer, buffer);
+    if saved.used {
+        editor.resume(writer, &saved);
+    }
+    edit_line(writer, editor, prompt, true)
+}
+
+fn edit_line<'a>(writer: &mut vga_buffer::Writer, mut editor: LineEditor<'a>, prompt: &str, shell: bool) -> Option<&'a str> {
+    let mut last_was_tab = false;
+
+    let mut history_index = history::len();
+    let mut draft = [0u8; DRAFT_LEN];
+    let mut draft_len = 0;
+
+    let mut searching = false;
+    let mut query = [0u8; QUERY_LEN];
+    let mut query_len = 0usize;
+    let mut found: Option<usize> = None;
+
+    let len = loop {
+        let KeyEvent { key, modifiers, .. } = keyboard::read_key();
+
+        match key {
+            _ if key.is_modifier() => continue,
+            Key::PageUp if modifiers.shift() => { writer.scroll_back(vga_buffer::height() / 2); continue; }
+            Key::PageDown if modifiers.shift() => { writer.scroll_forward(vga_buffer::height() / 2); continue; }
+            _ => writer.snap_to_bottom(),
+        }
+
+        if modifiers.alt {
+            match key {
+                Key::F(n) if shell && (1..=CONSOLE_COUNT as u8).contains(&n) => {
+                    let current = console::active();
+                    if searching {
+                        editor.render(writer, 0);
+                    }
+                    editor.suspend(current);
+                    if console::switch((n - 1) as usize) {
+                        return None;
+                    }
+                    *suspended(current) = NOT_SUSPENDED;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let tab_pressed = key == Key::Tab && !modifiers.ctrl();
+        let double_tab = tab_pressed && last_was_tab;
+        last_was_tab = tab_pressed;
+
+        if searching {
+            match key {
+                Key::Enter | Key::Escape | Key::Home | Key::Up | Key::Left | Key::Right | Key::End | Key::Down => {
+                    searching = false;
+                    match found.and_then(history::get) {
+                        Some(entry) => {
+                            history_index = found.unwrap_or(history_index);
+                            editor.set_text(writer, entry);
+                        }
+                        None => editor.render(writer, 0),
+                    }
+                    if key == Key::Enter {
+                        break editor.finish(writer);
+                    }
+                    continue;
+                }
+                Key::Backspace => {
+                    query_len = cp437::previous_char_start(&query, query_len);
+                    found = history::search(core::str::from_utf8(&query[..query_len]).unwrap_or(""), history::len());
+                }
+                _ if modifiers.ctrl() => {
+                    if key == Key::Char('r') || key == Key::Char('R') {
+                        let before = found.unwrap_or(history::len());
+                        if let Some(index) = history::search(core::str::from_utf8(&query[..query_len]).unwrap_or(""), before) {
+                            found = Some(index);
+                        }
+                    }
+                }
+                Key::Char(c) => {
+                    if query_len + c.len_utf8() <= query.len() {
+                        query_len += c.encode_utf8(&mut query[query_len..]).len();
+                        let before = found.map(|index| index + 1).unwrap_or(history::len());
+                        found = history::search(core::str::from_utf8(&query[..query_len]).unwrap_or(""), before);
+                    }
+                }
+                _ => {}
+            }
+            let mut display = [0u8; 320];
+            let n = search_display(&query[..query_len], found, &mut display);
+            editor.render_text(writer, &display[..n], 0, n);
+            continue;
+        }
+
+        if modifiers.ctrl() {
+            let letter = match key {
+                Key::Char(c) => Some(c.to_ascii_lowercase()),
+                _ => None,
+            };
+            match letter {
+                Some('a') => editor.move_cursor(writer, 0),
+                Some('e') => editor.move_cursor(writer, editor.len),
+                Some('u') => editor.delete_range(writer, 0, editor.cursor),
+                Some('k') => {
+                    let (cursor, len) = (editor.cursor, editor.len);
+                    editor.delete_range(writer, cursor, len);
+                }
+                Some('w') => {
+                    let (start, cursor) = (editor.word_start(), editor.cursor);
+                    editor.delete_range(writer, start, cursor);
+                }
+                Some('r') if shell => {
+                    searching = true;
+                    query_len = 0;
+                    found = None;
+                    let mut display = [0u8; 320];
+                    let n = search_display(&query[..query_len], found, &mut display);
+                    editor.render_text(writer, &display[..n], 0, n);
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match key {
+            Key::Enter => break editor.finish(writer),
+            Key::Backspace => {
+                if editor.cursor > 0 {
+                    let cursor = editor.cursor;
+                    editor.delete_range(writer, cursor - 1, cursor);
+                }
+            }
+            Key::Delete => {
+                if editor.cursor < editor.len {
+                    let cursor = editor.cursor;
+                    editor.delete_range(writer, cursor, cursor + 1);
+                }
+            }
+            Key::Left => {
+                let cursor = editor.cursor.saturating_sub(1);
+                editor.move_cursor(writer, cursor);
+            }
+            Key::Right => {
+                let cursor = editor.cursor + 1;
+                editor.move_cursor(writer, cursor);
+            }
+            Key::Home => editor.move_cursor(writer, 0),
+            Key::End => editor.move_cursor(writer, editor.len),
+            Key::Tab if shell => complete(writer, &mut editor, prompt, double_tab),
+            Key::Up if shell => {
+                if history_index > 0 {
+                    if history_index == history::len() {
+                        draft_len = editor.len.min(draft.len());
+                        draft[..draft_len].copy_from_slice(&editor.buffer[..draft_len]);
+                    }
+                    history_index -= 1;
+                    if let Some(entry) = history::get(history_index) {
+                        editor.set_text(writer, entry);
+                    }
+                }
+            }
+            Key::Down if shell => {
+                if history_index < history::len() {
+                    history_index += 1;
+                    match history::get(history_index) {
+                        Some(entry) => editor.set_text(writer, entry),
+                        None => editor.set_content(writer, &draft[..draft_len]),
+                    }
+                }
+            }
+            Key::Char(c) => editor.insert(writer, cp437::from_char(c)),
+            _ => {}
+        }
+    };
+
+    let LineEditor { buffer, .. } = editor;
+    Some(unsafe { core::str::from_utf8_unchecked(&buffer[..len]) })
+}
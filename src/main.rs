@@ -1,325 +1,372 @@
 #![no_std]
 #![no_main]
 #![feature(alloc_error_handler)]
+#![feature(abi_x86_interrupt)]
 use core::panic::PanicInfo;
 mod file_system;
 mod vga_buffer;
+mod cp437;
+mod ansi;
+mod console;
 mod code_system;
 mod syntax;
 mod settings;
+mod port;
+mod pci;
+mod memory;
+mod timer;
+mod rtl8139;
+mod net;
+mod tftp;
+mod history;
+mod line_editor;
+mod completion;
+mod commands;
+mod args;
+mod io;
+mod shell;
+mod env;
+mod script;
+mod textutils;
+mod pager;
+mod graphics;
+mod font;
+mod keyboard;
+mod keymap;
+mod kmap;
+mod rtc;
+mod statusbar;
+mod interrupts;
+
+use commands::Command;
+use keyboard::Key;
+use io::Sink;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    let mut w = vga_buffer::Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut vga_buffer::Buffer) },
-    };
+    x86_64::instructions::interrupts::disable();
+    graphics::set_mode(graphics::text_mode());
+    let w = console::writer();
+    w.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
     w.clear_screen();
     w.write_string("!!! PANIC !!!\n");
     if let Some(location) = info.location() {
-        w.write_string("panic at ");
-        w.write_string(location.file());
-        w.write_string(":");
-        let mut buf = [0u8; 20];
-        w.write_string(&vga_buffer::int_to_string(location.line() as usize, &mut buf));
-        w.write_string("\n");
+        writeln!(w, "panic at {}:{}:{}", location.file(), location.line(), location.column());
     } else {
         w.write_string("panic location unknown.\n");
     }
-    let msg = info.message();
-    w.write_string("panic message: ");
-    w.write_string(msg.as_str().unwrap_or("no known message"));
-    w.write_string("\n");
+    writeln!(w, "panic message: {}", info.message());
     loop {}
 }
 
-fn read_scancode() -> u8 {
-    loop {
-        let mut status: u8;
-        unsafe { core::arch::asm!("in al, 0x64", out("al") status, options(nomem, nostack, preserves_flags)); }
-        if status & 1 != 0 {
-            let mut sc: u8;
-            unsafe { core::arch::asm!("in al, 0x60", out("al") sc, options(nomem, nostack, preserves_flags)); }
-            return sc;
-        }
-    }
-}
+fn display_highlighted_content(writer: &mut vga_buffer::Writer, content: &[u8], highlighter: &syntax::SyntaxHighlighter) {
+    let content_str = unsafe { core::str::from_utf8_unchecked(content) };
 
-fn read_key() -> u8 {
-    loop {
-        let sc = read_scancode();
-        if sc > 0 && sc < 0x80 { 
-            loop {
-                if read_scancode() == sc | 0x80 {
-                    break;
-                }
-            }
-            return sc;
-        }
+    for line in content_str.lines() {
+        syntax::highlight_line(line, writer, highlighter);
+        writer.write_byte(b'\n');
     }
 }
 
-fn scancode_to_char(sc: u8) -> Option<char> {
-    settings::scancode_to_char(sc, false) 
+fn cmd_help(io: &mut io::Io, argv: &[&str]) -> i32 {
+    pager::paged(io, |io| write_help(io, argv))
 }
 
-fn read_line<'a>(writer: &mut vga_buffer::Writer, buffer: &'a mut [u8]) -> &'a str {
-    let mut i = 0;
-    let mut shift_pressed = false;
-
-    loop {
-        let sc = read_scancode();
-
-        match sc {
-            0x2A | 0x36 => { shift_pressed = true; continue; } 
-            0xAA | 0xB6 => { shift_pressed = false; continue; } 
-            _ => {}
-        }
-
-        if sc >= 0x80 { continue; }
-
-        match sc {
-            0x1C => { 
-                writer.write_byte(b'\n');
-                break;
-            }
-            0x0E => { 
-                if i > 0 {
-                    i -= 1;
-                    if writer.column_position > 0 {
-                        writer.column_position -= 1;
-                        writer.write_byte(b' ');
-                        writer.column_position -= 1;
-                    }
-                }
+fn write_help(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if let Some(&name) = argv.get(1) {
+        match commands::find(name) {
+            Some(command) => {
+                commands::write_details(io, &command);
+                return 0;
             }
-            0x3A => { 
-                let mut settings = settings::get_settings();
-                settings.caps_lock_enabled = !settings.caps_lock_enabled;
-                settings::set_settings(settings);
-            }
-            _ => {
-                if i < buffer.len() - 1 {
-                    if let Some(c) = settings::scancode_to_char(sc, shift_pressed) {
-                        buffer[i] = c as u8;
-                        writer.write_byte(c as u8);
-                        i += 1;
-                    }
-                }
+            None => {
+                io.write_string("No help for unknown command: '");
+                io.write_string(name);
+                io.write_string("'\n");
+                return 1;
             }
         }
     }
-    buffer[i] = 0;
-    unsafe { core::str::from_utf8_unchecked(&buffer[0..i]) }
-}
 
-fn parse_command<'a>(input: &'a str) -> (&'a str, Option<&'a str>) {
-    let mut parts = input.trim().splitn(2, ' ');
-    let command = parts.next().unwrap_or("");
-    let arg = parts.next();
-    (command, arg)
-}
-
-fn display_highlighted_content(writer: &mut vga_buffer::Writer, content: &[u8], highlighter: &syntax::SyntaxHighlighter) {
-    let content_str = unsafe { core::str::from_utf8_unchecked(content) };
-
-    for line in content_str.lines() {
-        syntax::highlight_line(line, writer, highlighter);
-        writer.write_byte(b'\n');
+    io.write_string("Anomia OS Commands:\n");
+    for index in 0..commands::count() {
+        if let Some(command) = commands::get(index) {
+            commands::write_summary_line(io, &command);
+        }
     }
+    io.write_string("\nCODE Language Instructions:\n");
+    io.write_string("  mov reg, value  - Load immediate value into register (eax,ebx,ecx,edx)\n");
+    io.write_string("  add eax, ebx    - Add EBX to EAX\n");
+    io.write_string("  sub eax, ebx    - Subtract EBX from EAX\n");
+    io.write_string("  cmp eax, value  - Compare EAX with immediate value\n");
+    io.write_string("  je offset       - Jump if equal (after CMP)\n");
+    io.write_string("  jmp offset      - Unconditional jump\n");
+    io.write_string("  halt            - Stop program execution\n");
+    io.write_string("  nop             - No operation\n");
+    io.write_string("  ; comment       - Comment line\n");
+    io.write_string("\nShell Features:\n");
+    io.write_string("  cmd > file      - Write command output to a file (>> appends)\n");
+    io.write_string("  cmd < file      - Read command input from a file\n");
+    io.write_string("  cmd1 | cmd2     - Pipe the output of cmd1 into cmd2\n");
+    io.write_string("  \"a b\" 'a b' a\\ b - Quote or escape spaces in arguments\n");
+    io.write_string("  $VAR ${VAR} $?  - Expand variables and the last exit status\n");
+    io.write_string("  sh script.sh    - Run a script with if/else/fi and while/done\n");
+    io.write_string("\nKeyboard Features:\n");
+    io.write_string("  Hardware Caps Lock - Press Caps Lock key to toggle uppercase\n");
+    io.write_string("  Shift Support   - Hold Shift for symbols (Shift+8 = *, etc.)\n");
+    io.write_string("  6 Layouts       - US, UK, German, French, Spanish, Dvorak\n");
+    io.write_string("  loadkeys file   - Load a keymap file (.kmap) from the file system\n");
+    io.write_string("  Full Symbols    - All punctuation and special characters\n");
+    io.write_string("  AltGr           - Third-level symbols such as €, @, [ ] and { }\n");
+    io.write_string("  Dead Keys       - Accent then letter: ´ + e = é, ¨ + u = ü, ~ + n = ñ\n");
+    io.write_string("  Spanish chars   - ñ, ´, ¡, ¿, ç and more\n");
+    io.write_string("  Line Editing    - Left/Right, Home/End, Delete, Ctrl+A/E/U/K/W\n");
+    io.write_string("  Tab             - Complete commands, files and directories (Tab Tab lists)\n");
+    io.write_string("  Up/Down         - Recall previous commands (!! and !n also work)\n");
+    io.write_string("  Ctrl+R          - Reverse search through the command history\n");
+    io.write_string("  Shift+PgUp/PgDn - Scroll back through earlier output\n");
+    io.write_string("  Alt+F1..F4      - Switch between the four virtual consoles\n");
+    io.write_string("  Pager           - Space/Enter/b to scroll, /text to search, q to quit\n");
+    io.write_string("\nEditor Features:\n");
+    io.write_string("  Syntax Colors   - Instructions (blue), registers (green), numbers (yellow)\n");
+    io.write_string("  3 Themes        - Default, Dark, Retro Green\n");
+    io.write_string("  Real-time       - Colors appear as you type in .code files\n");
+    io.write_string("\nType 'help <command>' for details about a command.\n");
+    0
 }
 
-fn cmd_help(writer: &mut vga_buffer::Writer) {
-    writer.write_string("Anomia OS Commands:\n");
-    writer.write_string("  ls, dir         - List files and directories\n");
-    writer.write_string("  cd <dir>        - Change current directory\n");
-    writer.write_string("  cat <file>      - Display file content\n");
-    writer.write_string("  nano <file>     - Text editor (syntax highlighting for .code files)\n");
-    writer.write_string("  write <file>    - Create/overwrite a file with one line of text\n");
-    writer.write_string("  rm, del <file>  - Delete a file\n");
-    writer.write_string("  run <file>      - Execute a CODE assembly program\n");
-    writer.write_string("  sample          - Create a sample CODE program (demo.code)\n");
-    writer.write_string("  settings        - Configure keyboard, editor, and display options\n");
-    writer.write_string("  tests           - Run system diagnostics\n");
-    writer.write_string("  date            - Shows the current date and time\n");
-    writer.write_string("  clear           - Clear the screen\n");
-    writer.write_string("  exit, reboot    - Halts the CPU\n");
-    writer.write_string("\nCODE Language Instructions:\n");
-    writer.write_string("  mov reg, value  - Load immediate value into register (eax,ebx,ecx,edx)\n");
-    writer.write_string("  add eax, ebx    - Add EBX to EAX\n");
-    writer.write_string("  sub eax, ebx    - Subtract EBX from EAX\n");
-    writer.write_string("  cmp eax, value  - Compare EAX with immediate value\n");
-    writer.write_string("  je offset       - Jump if equal (after CMP)\n");
-    writer.write_string("  jmp offset      - Unconditional jump\n");
-    writer.write_string("  halt            - Stop program execution\n");
-    writer.write_string("  nop             - No operation\n");
-    writer.write_string("  ; comment       - Comment line\n");
-    writer.write_string("\nKeyboard Features:\n");
-    writer.write_string("  Hardware Caps Lock - Press Caps Lock key to toggle uppercase\n");
-    writer.write_string("  Shift Support   - Hold Shift for symbols (Shift+8 = *, etc.)\n");
-    writer.write_string("  5 Layouts       - QWERTY, AZERTY, QWERTZ, Spanish, Dvorak\n");
-    writer.write_string("  Full Symbols    - All punctuation and special characters\n");
-    writer.write_string("  Spanish chars   - ñ, ´, ¡, ¿, ç and more\n");
-    writer.write_string("\nEditor Features:\n");
-    writer.write_string("  Syntax Colors   - Instructions (blue), registers (green), numbers (yellow)\n");
-    writer.write_string("  3 Themes        - Default, Dark, Retro Green\n");
-    writer.write_string("  Real-time       - Colors appear as you type in .code files\n");
+fn cmd_ls(io: &mut io::Io, argv: &[&str]) -> i32 {
+    pager::paged(io, |io| write_listing(io, argv))
 }
 
-fn cmd_ls(writer: &mut vga_buffer::Writer) {
-    writer.write_string("Directory listing:\n");
+fn write_listing(io: &mut io::Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("l", &["long"]).is_err() {
+        io.write_string("Usage: ls [-l | --long]\n");
+        return 2;
+    }
+    let long = flags.has('l') || flags.has_long("long");
+
+    io.write_string("Directory listing:\n");
 
     file_system::with_fs(|fs| {
         let (folders, files) = fs.list_current_directory();
         let mut total_count = 0;
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black);
+        io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black));
         for folder_name_opt in folders.iter() {
             if let Some(folder_name) = folder_name_opt {
                 if let Ok(folder_str) = core::str::from_utf8(folder_name) {
-                    writer.write_string("  [DIR] ");
-                    writer.write_string(folder_str);
-                    writer.write_byte(b'\n');
+                    io.write_string("  [DIR] ");
+                    io.write_string(folder_str);
+                    io.write_byte(b'\n');
                     total_count += 1;
                 }
             }
         }
 
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+        io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
         let all_files = fs.list_all_files();
         for file_name_option in &all_files {
             if let Some(file_name_bytes) = file_name_option {
                 if let Ok(file_str) = core::str::from_utf8(file_name_bytes) {
-                    writer.write_string("  - ");
-                    writer.write_string(file_str);
-                    writer.write_byte(b'\n');
+                    io.write_string("  - ");
+                    if long {
+                        let size = fs.read_file(file_str).map(|data| data.len()).unwrap_or(0);
+                        write!(io, "{:>5}  ", size);
+                    }
+                    io.write_string(file_str);
+                    io.write_byte(b'\n');
                     total_count += 1;
                 }
             }
         }
 
         if total_count == 0 {
-            writer.write_string("  (Empty directory)\n");
+            io.write_string("  (Empty directory)\n");
         } else {
-            writer.write_string("\nTotal items: ");
-            let mut buf = [0u8; 20];
-            writer.write_string(&vga_buffer::int_to_string(total_count, &mut buf));
-            writer.write_string("\n");
+            writeln!(io, "\nTotal items: {}", total_count);
         }
     });
+    0
 }
 
-fn cmd_cat(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
-    if let Some(name) = filename {
+fn write_content(io: &mut io::Io, data: &[u8]) {
+    for &byte in data {
+        io.write_byte(byte);
+    }
+    if data.last() != Some(&b'\n') {
+        io.write_byte(b'\n');
+    }
+}
+
+fn cmd_cat(io: &mut io::Io, argv: &[&str]) -> i32 {
+    pager::paged(io, |io| write_files(io, argv))
+}
+
+fn write_files(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if argv.len() < 2 {
+        return match io.stdin.as_mut().map(|stdin| stdin.read_all()) {
+            Some(data) => {
+                write_content(io, data);
+                0
+            }
+            None => {
+                io.write_string("Usage: cat <filename>...\n");
+                2
+            }
+        };
+    }
+    let mut status = 0;
+    for &name in &argv[1..] {
         file_system::with_fs(|fs| {
             match fs.read_file(name) {
-                Ok(data) => {
-                    for &byte in data {
-                        writer.write_byte(byte);
-                    }
-                    writer.write_byte(b'\n');
-                },
-                Err(_) => writer.write_string("Error: File not found.\n"),
+                Ok(data) => write_content(io, data),
+                Err(_) => {
+                    io.write_string("Error: File not found: ");
+                    io.write_string(name);
+                    io.write_string("\n");
+                    status = 1;
+                }
             }
         });
-    } else {
-        writer.write_string("Usage: cat <filename>\n");
     }
+    status
 }
 
-fn cmd_rm(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
-    if let Some(name) = filename {
+fn cmd_rm(io: &mut io::Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("r", &[]).is_err() || flags.positional().is_empty() {
+        io.write_string("Usage: rm [-r] <filename>...\n");
+        return 2;
+    }
+    let recursive = flags.has('r');
+
+    let mut status = 0;
+    for &name in flags.positional() {
         file_system::with_fs_mut(|fs| {
-            match fs.delete_file(name) {
-                Ok(_) => {
-                    writer.write_string("File '");
-                    writer.write_string(name);
-                    writer.write_string("' deleted.\n");
-                },
-                Err(_) => writer.write_string("Error: File could not be deleted.\n"),
+            if fs.delete_file(name).is_ok() {
+                io.write_string("File '");
+                io.write_string(name);
+                io.write_string("' deleted.\n");
+            } else if recursive && fs.delete_folder(name).is_ok() {
+                io.write_string("Folder '");
+                io.write_string(name);
+                io.write_string("' deleted.\n");
+            } else {
+                io.write_string("Error: Could not delete '");
+                io.write_string(name);
+                io.write_string("'.\n");
+                status = 1;
             }
         });
-    } else {
-        writer.write_string("Usage: rm <filename>\n");
     }
+    status
 }
 
-fn cmd_write(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
-    if let Some(name) = filename {
-        writer.write_string("Enter text to write and press Enter:\n> ");
+fn cmd_write(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if let Some(&name) = argv.get(1) {
         let mut buffer = [0u8; 1024];
-        let input = read_line(writer, &mut buffer);
+        let input = match io.stdin.as_mut() {
+            Some(stdin) => stdin.read_all(),
+            None => {
+                io.write_string("Enter text to write and press Enter:\n> ");
+                line_editor::read_line(io.console, &mut buffer).as_bytes()
+            }
+        };
 
-        file_system::with_fs_mut(|fs| {
-            match fs.write_file(name, input.as_bytes()) {
-                 Ok(_) => writer.write_string("File written successfully.\n"),
-                 Err(_) => writer.write_string("Error: Could not write file.\n"),
+        match file_system::with_fs_mut(|fs| fs.write_file(name, input)) {
+            Ok(_) => {
+                io.write_string("File written successfully.\n");
+                0
             }
-        });
+            Err(_) => {
+                io.write_string("Error: Could not write file.\n");
+                1
+            }
+        }
     } else {
-        writer.write_string("Usage: write <filename>\n");
+        io.write_string("Usage: write <filename>\n");
+        2
     }
 }
 
-fn cmd_mkdir(writer: &mut vga_buffer::Writer, foldername: Option<&str>) {
-    if let Some(name) = foldername {
+fn cmd_mkdir(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if argv.len() < 2 {
+        io.write_string("Usage: mkdir <foldername>...\n");
+        return 2;
+    }
+    let mut status = 0;
+    for &name in &argv[1..] {
         file_system::with_fs_mut(|fs| {
             match fs.create_folder(name) {
                 Ok(_) => {
-                    writer.write_string("Folder '");
-                    writer.write_string(name);
-                    writer.write_string("' created successfully.\n");
+                    io.write_string("Folder '");
+                    io.write_string(name);
+                    io.write_string("' created successfully.\n");
                 },
-                Err(_) => writer.write_string("Error: Could not create folder.\n"),
+                Err(_) => {
+                    io.write_string("Error: Could not create folder.\n");
+                    status = 1;
+                }
             }
         });
-    } else {
-        writer.write_string("Usage: mkdir <foldername>\n");
     }
+    status
 }
 
-fn cmd_rmdir(writer: &mut vga_buffer::Writer, foldername: Option<&str>) {
-    if let Some(name) = foldername {
+fn cmd_rmdir(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if argv.len() < 2 {
+        io.write_string("Usage: rmdir <foldername>...\n");
+        return 2;
+    }
+    let mut status = 0;
+    for &name in &argv[1..] {
         file_system::with_fs_mut(|fs| {
             match fs.delete_folder(name) {
                 Ok(_) => {
-                    writer.write_string("Folder '");
-                    writer.write_string(name);
-                    writer.write_string("' deleted successfully.\n");
+                    io.write_string("Folder '");
+                    io.write_string(name);
+                    io.write_string("' deleted successfully.\n");
                 },
-                Err(_) => writer.write_string("Error: Could not delete folder.\n"),
+                Err(file_system::FileSystemError::Busy) => {
+                    io.write_string("Error: A console is still in that folder.\n");
+                    status = 1;
+                },
+                Err(_) => {
+                    io.write_string("Error: Could not delete folder.\n");
+                    status = 1;
+                }
             }
         });
-    } else {
-        writer.write_string("Usage: rmdir <foldername>\n");
     }
+    status
 }
 
-fn cmd_cd(writer: &mut vga_buffer::Writer, path: Option<&str>) {
-    if let Some(dir_path) = path {
-        file_system::with_fs_mut(|fs| {
-            match fs.change_directory(dir_path) {
-                Ok(_) => {},
-                Err(_) => {
-                    writer.write_string("Error: Directory not found: ");
-                    writer.write_string(dir_path);
-                    writer.write_string("\n");
-                }
+fn cmd_cd(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if let Some(&dir_path) = argv.get(1) {
+        match file_system::with_fs_mut(|fs| fs.change_directory(dir_path)) {
+            Ok(_) => 0,
+            Err(_) => {
+                io.write_string("Error: Directory not found: ");
+                io.write_string(dir_path);
+                io.write_string("\n");
+                1
             }
-        });
+        }
     } else {
-        writer.write_string("Usage: cd <directory>\n");
+        io.write_string("Usage: cd <directory>\n");
+        2
     }
 }
 
-fn cmd_nano(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
-    let filename_str = if let Some(name) = filename {
+fn cmd_nano(io: &mut io::Io, argv: &[&str]) -> i32 {
+    let writer = &mut *io.console;
+    let filename_str = if let Some(&name) = argv.get(1) {
         name
     } else {
         writer.write_string("Usage: nano <filename>\n");
-        return;
+        return 2;
     };
 
     let is_code_file = filename_str.ends_with(".code");
@@ -337,18 +384,18 @@ fn cmd_nano(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
     }
     writer.write_string(" (ESC=Save&Exit) ");
 
-    for _ in writer.column_position..vga_buffer::BUFFER_WIDTH {
+    for _ in writer.column_position..vga_buffer::width() {
         writer.write_byte(b' ');
     }
     writer.write_byte(b'\n');
 
     writer.color_code = syntax::get_editor_border_color(theme);
-    for _ in 0..vga_buffer::BUFFER_WIDTH {
+    for _ in 0..vga_buffer::width() {
         writer.write_byte(b'-');
     }
     writer.write_byte(b'\n');
 
-    let mut content_buf = [0u8; 4096];
+    let mut content_buf = [0u8; crate::file_system::MAX_FILE_SIZE];
     let mut content_len = 0;
 
     crate::file_system::with_fs(|fs| {
@@ -368,31 +415,22 @@ fn cmd_nano(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
         }
     });
 
-    let mut shift_pressed = false;
-
     loop {
-        let sc = read_scancode();
+        let event = keyboard::read_key();
+        let modifiers = event.modifiers;
 
-        match sc {
-            0x2A | 0x36 => { shift_pressed = true; continue; }
-            0xAA | 0xB6 => { shift_pressed = false; continue; }
-            _ => {}
-        }
-
-        if sc >= 0x80 { continue; }
-
-        match sc {
-            0x01 => break, 
-            0x1C => { 
+        match event.key {
+            Key::Escape => break,
+            Key::Enter => {
                 if content_len < content_buf.len() {
                     content_buf[content_len] = b'\n';
                     content_len += 1;
                     writer.write_byte(b'\n');
                 }
             }
-            0x0E => { 
+            Key::Backspace => {
                 if content_len > 0 {
-                    content_len -= 1;
+                    content_len = cp437::previous_char_start(&content_buf, content_len);
                     if writer.column_position > 0 {
                         writer.column_position -= 1;
                         writer.write_byte(b' ');
@@ -400,169 +438,243 @@ fn cmd_nano(writer: &mut vga_buffer::Writer, filename: Option<&str>) {
                     }
                 }
             }
-            0x3A => { 
-                let mut settings = settings::get_settings();
-                settings.caps_lock_enabled = !settings.caps_lock_enabled;
-                settings::set_settings(settings);
-            }
-            _ => {
-                if content_len < content_buf.len() {
-                    if let Some(c) = settings::scancode_to_char(sc, shift_pressed) {
-                        content_buf[content_len] = c as u8;
-                        content_len += 1;
-
-                        if is_code_file && settings.syntax_highlighting {
-                            let mut tmp = [0u8; 4];                    
-                            let token_str = c.encode_utf8(&mut tmp);   
-                            let token_type = highlighter.classify_token(token_str);
-                            writer.color_code = token_type.get_color(theme);
-                        } else {
-                            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
-                        }
-                        writer.write_byte(c as u8);
+            Key::Char(c) if !modifiers.ctrl() && !modifiers.alt => {
+                if content_len + c.len_utf8() <= content_buf.len() {
+                    content_len += c.encode_utf8(&mut content_buf[content_len..]).len();
+
+                    if is_code_file && settings.syntax_highlighting {
+                        let mut tmp = [0u8; 4];                    
+                        let token_str = c.encode_utf8(&mut tmp);   
+                        let token_type = highlighter.classify_token(token_str);
+                        writer.color_code = token_type.get_color(theme);
+                    } else {
+                        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
                     }
+                    writer.write_char(c);
                 }
             }
+            _ => {}
         }
     }
 
     writer.color_code = syntax::get_editor_status_color(theme);
     writer.write_string("\n");
-    for _ in 0..vga_buffer::BUFFER_WIDTH {
+    for _ in 0..vga_buffer::width() {
         writer.write_byte(b'-');
     }
     writer.write_string(" Saving... ");
 
-    crate::file_system::with_fs_mut(|fs| {
+    let status = crate::file_system::with_fs_mut(|fs| {
         match fs.write_file(filename_str, &content_buf[..content_len]) {
-            Ok(_) => writer.write_string("Done! "),
-            Err(_) => writer.write_string("Failed! "),
+            Ok(_) => { writer.write_string("Done! "); 0 },
+            Err(_) => { writer.write_string("Failed! "); 1 },
         }
     });
 
     writer.write_string("Press any key to continue ");
-    read_key();
+    keyboard::read_key();
     writer.clear_screen();
     writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+    status
 }
 
-fn cmd_run(writer: &mut vga_buffer::Writer, fs: &file_system::OsFileSystem, filename: Option<&str>) {
-    if let Some(name) = filename {
-        writer.write_string("Executing CODE file: ");
-        writer.write_string(name);
-        writer.write_string("\n");
+fn cmd_edit(io: &mut io::Io, argv: &[&str]) -> i32 {
+    let editor = env::get("EDITOR").filter(|e| !e.is_empty() && *e != "edit").unwrap_or("nano");
+    match commands::find(editor) {
+        Some(command) => {
+            let mut editor_argv = [""; args::MAX_ARGS];
+            editor_argv[0] = editor;
+            editor_argv[1..argv.len()].copy_from_slice(&argv[1..]);
+            (command.handler)(io, &editor_argv[..argv.len()])
+        }
+        None => {
+            io.write_string("edit: editor not found: ");
+            io.write_string(editor);
+            io.write_string("\n");
+            127
+        }
+    }
+}
+
+fn cmd_run(io: &mut io::Io, argv: &[&str]) -> i32 {
+    if let Some(&name) = argv.get(1) {
+        io.write_string("Executing CODE file: ");
+        io.write_string(name);
+        io.write_string("\n");
 
-        match code_system::execute_code_file(name, fs, writer) {
-            Ok(_) => {}, 
+        match file_system::with_fs(|fs| code_system::execute_code_file(name, fs, io)) {
+            Ok(_) => 0,
             Err(e) => {
-                writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
-                writer.write_string("Execution error: ");
-                writer.write_string(e);
-                writer.write_string("\n");
-                writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+                io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black));
+                io.write_string("Execution error: ");
+                io.write_string(e);
+                io.write_string("\n");
+                io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
+                1
             }
         }
     } else {
-        writer.write_string("Usage: run <filename.code>\n");
+        io.write_string("Usage: run <filename.code>\n");
+        2
     }
 }
 
-fn cmd_sample(writer: &mut vga_buffer::Writer, fs: &mut file_system::OsFileSystem) {
-    writer.write_string("Creating sample CODE program 'demo.code'...\n");
+fn cmd_sample(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    io.write_string("Creating sample CODE program 'demo.code'...\n");
     let sample_code = code_system::create_sample_program();
 
-    match fs.write_file("demo.code", sample_code.as_bytes()) {
+    match file_system::with_fs_mut(|fs| fs.write_file("demo.code", sample_code.as_bytes())) {
         Ok(_) => {
-            writer.write_string("Sample program created successfully.\n");
-            writer.write_string("Run it with: run demo.code\n");
+            io.write_string("Sample program created successfully.\n");
+            io.write_string("Run it with: run demo.code\n");
+            0
         },
         Err(_) => {
-            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
-            writer.write_string("Error: Could not create sample file.\n");
-            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+            io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black));
+            io.write_string("Error: Could not create sample file.\n");
+            io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
+            1
         }
     }
 }
 
-fn cmd_settings(writer: &mut vga_buffer::Writer) {
-    settings::show_settings_menu(writer);
+fn cmd_settings(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    settings::show_settings_menu(io.console);
+    io.console.clear_screen();
+    0
+}
+
+fn cmd_clear(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    io.console.clear_screen();
+    0
+}
+
+fn cmd_tests(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    vga_buffer::color_test();
+    vga_buffer::ascii_test();
+    vga_buffer::math_test();
+    vga_buffer::file_system_test();
+    vga_buffer::network_test();
+    io.console.write_string("System tests complete.\n");
+    0
+}
+
+fn cmd_date(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    io.write_string("Current time: Sat, 27 Sep 2025 01:26 AM CEST\n");
+    0
+}
+
+fn cmd_exit(io: &mut io::Io, _argv: &[&str]) -> i32 {
+    shutdown(io.console)
+}
+
+fn shutdown(writer: &mut vga_buffer::Writer) -> ! {
     writer.clear_screen();
+    writer.write_string("Shutting down Anomia OS. Goodbye!");
+
+    loop {
+        unsafe { core::arch::asm!("cli; hlt", options(nomem, nostack, preserves_flags)); }
+    }
+}
+
+fn register_builtin_commands() {
+    commands::register_all(&[
+        Command { name: "help", aliases: &[], usage: "help [command]", summary: "Show this help or details for a command", handler: cmd_help },
+        Command { name: "ls", aliases: &["dir"], usage: "ls [-l]", summary: "List files and directories", handler: cmd_ls },
+        Command { name: "cd", aliases: &[], usage: "cd <dir>", summary: "Change current directory", handler: cmd_cd },
+        Command { name: "cat", aliases: &[], usage: "cat <file>...", summary: "Display file content", handler: cmd_cat },
+        Command { name: "nano", aliases: &[], usage: "nano <file>", summary: "Text editor (syntax highlighting for .code)", handler: cmd_nano },
+        Command { name: "edit", aliases: &[], usage: "edit <file>", summary: "Open a file in $EDITOR (default nano)", handler: cmd_edit },
+        Command { name: "write", aliases: &[], usage: "write <file>", summary: "Create/overwrite a file with one line of text", handler: cmd_write },
+        Command { name: "rm", aliases: &["del"], usage: "rm [-r] <file>...", summary: "Delete files (-r also folders)", handler: cmd_rm },
+        Command { name: "mkdir", aliases: &[], usage: "mkdir <dir>...", summary: "Create a directory", handler: cmd_mkdir },
+        Command { name: "rmdir", aliases: &[], usage: "rmdir <dir>...", summary: "Delete a directory", handler: cmd_rmdir },
+        Command { name: "run", aliases: &[], usage: "run <file.code>", summary: "Execute a CODE assembly program", handler: cmd_run },
+        Command { name: "sample", aliases: &[], usage: "sample", summary: "Create a sample CODE program (demo.code)", handler: cmd_sample },
+        Command { name: "settings", aliases: &["config"], usage: "settings", summary: "Configure keyboard, editor and display", handler: cmd_settings },
+        Command { name: "tests", aliases: &[], usage: "tests", summary: "Run system diagnostics", handler: cmd_tests },
+        Command { name: "date", aliases: &[], usage: "date", summary: "Shows the current date and time", handler: cmd_date },
+        Command { name: "clear", aliases: &[], usage: "clear", summary: "Clear the screen", handler: cmd_clear },
+        Command { name: "exit", aliases: &["reboot"], usage: "exit", summary: "Halts the CPU", handler: cmd_exit },
+    ]);
 }
 
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn _start() -> ! {
-    let mut writer = vga_buffer::Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut vga_buffer::Buffer) },
-    };
+pub unsafe extern "C" fn _start(boot_info: &'static bootloader::BootInfo) -> ! {
+    memory::init(boot_info);
+    graphics::init();
+    console::init();
+    let writer = console::writer();
+    writer.set_cursor_shape(vga_buffer::CursorShape::Underline);
+
+    timer::init();
+    interrupts::init();
+    env::init();
+
+    register_builtin_commands();
+    net::register_commands();
+    tftp::register_commands();
+    history::register_commands();
+    env::register_commands();
+    script::register_commands();
+    textutils::register_commands();
+    pager::register_commands();
+    graphics::register_commands();
+    font::register_commands();
+    keymap::register_commands();
 
-    let mut fs = file_system::new_os_file_system();
     writer.clear_screen();
     writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black);
     writer.write_string("==== WELCOME TO ANOMIA OS ====\n");
     writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
-    writer.write_string("Type 'help' for a list of commands.\n\n");
-
-    let mut command_buffer = [0u8; 256];
-
-    loop {
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::LightGreen, vga_buffer::Color::Black);
-        writer.write_string("anomia> ");
-        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
-
-        let input = read_line(&mut writer, &mut command_buffer);
-        let (command, arg) = parse_command(input);
-
-        match command {
-            "help" => cmd_help(&mut writer),
-            "ls" | "dir" => cmd_ls(&mut writer),
-            "cd" => cmd_cd(&mut writer, arg),
-            "cat" => cmd_cat(&mut writer, arg),
-            "nano" => cmd_nano(&mut writer, arg),
-            "write" => cmd_write(&mut writer, arg),
-            "rm" | "del" => cmd_rm(&mut writer, arg),
-            "mkdir" => cmd_mkdir(&mut writer, arg),
-            "rmdir" => cmd_rmdir(&mut writer, arg),
-            "run" => {
-                let fs_ref: &file_system::OsFileSystem = unsafe { &*fs };
-                cmd_run(&mut writer, fs_ref, arg);
-            },
-            "sample" => {
-                let fs_mut: &mut file_system::OsFileSystem = unsafe { &mut *fs };
-                cmd_sample(&mut writer, fs_mut);
-            },
-            "settings" | "config" => cmd_settings(&mut writer),
-            "clear" => writer.clear_screen(),
-            "tests" => {
-                vga_buffer::color_test();
-                vga_buffer::ascii_test();
-                vga_buffer::math_test();
-                vga_buffer::file_system_test();
-                writer.write_string("System tests complete.\n");
-            },
-            "date" => writer.write_string("Current time: Sat, 27 Sep 2025 01:26 AM CEST\n"),
-            "exit" | "reboot" => break,
-            "" => {}
-            _ => {
-                writer.color_code =
-                    vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
-                writer.write_string("Unknown command: '");
-                writer.write_string(command);
-                writer.write_string("'\n");
-                writer.color_code = vga_buffer::ColorCode::new(
-                    vga_buffer::Color::White,
-                    vga_buffer::Color::Black,
-                );
+    match net::init() {
+        Ok(_) => {
+            writer.write_string("Network: eth0 up at ");
+            if let Some(config) = net::config() {
+                net::write_ip(writer, config.ip);
             }
+            writer.write_string("\n");
+        }
+        Err(e) => {
+            writer.write_string("Network: ");
+            writer.write_string(e);
+            writer.write_string("\n");
         }
     }
+    keymap::check_builtin(writer);
+    writeln!(writer, "Type 'help' for a list of commands.\n");
+
+    let _ = history::load();
+    script::run_startup(writer, script::RC_FILE);
+    script::run_startup(writer, env::PROFILE_FILE);
+    // The other consoles start from the session the startup scripts set up.
+    for index in 1..console::CONSOLE_COUNT {
+        env::copy_variables(0, index);
+        file_system::with_fs_mut(|fs| fs.copy_working_dir(0, index));
+    }
 
-    writer.clear_screen();
-    writer.write_string("Shutting down Anomia OS. Goodbye!");
+    let mut command_buffer = [0u8; 256];
+    let mut expanded_buffer = [0u8; 256];
 
     loop {
-        unsafe { core::arch::asm!("cli; hlt", options(nomem, nostack, preserves_flags)); }
+        let writer = console::writer();
+        let Some(mut input) = line_editor::read_command(writer, env::prompt(), &mut command_buffer) else {
+            continue;
+        };
+        match history::expand(input, &mut expanded_buffer) {
+            Ok(Some(expanded)) => {
+                writer.write_string(expanded);
+                writer.write_string("\n");
+                input = expanded;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                writer.write_string(e);
+                writer.write_string("\n");
+                continue;
+            }
+        }
+        history::push(input);
+        shell::execute(&mut io::Io::new(writer, None, None), input);
     }
 }
@@ -1,4 +1,15 @@
-pub Struct MemoryBlock {
+#![allow(dead_code)]
+
+use bootloader::bootinfo::MemoryRegionType;
+use bootloader::BootInfo;
+
+const PAGE_SIZE: u64 = 4096;
+
+static mut PHYSICAL_MEMORY_OFFSET: u64 = 0;
+// Bytes of RAM in use and in total, as reported by the bootloader's memory map.
+static mut MEMORY_USAGE: (u64, u64) = (0, 0);
+
+pub struct MemoryBlock {
     start_address: usize,
     size: usize,
     data: [u8; 1024],
@@ -16,3 +27,100 @@ impl MemoryBlock {
     }
 }
 
+pub fn init(boot_info: &BootInfo) {
+    unsafe { PHYSICAL_MEMORY_OFFSET = boot_info.physical_memory_offset; }
+
+    let (mut used, mut total) = (0, 0);
+    for region in boot_info.memory_map.iter() {
+        let size = region.range.end_addr() - region.range.start_addr();
+        match region.region_type {
+            MemoryRegionType::Usable => total += size,
+            MemoryRegionType::InUse
+            | MemoryRegionType::FrameZero
+            | MemoryRegionType::Kernel
+            | MemoryRegionType::KernelStack
+            | MemoryRegionType::PageTable
+            | MemoryRegionType::Bootloader
+            | MemoryRegionType::BootInfo
+            | MemoryRegionType::Package => {
+                used += size;
+                total += size;
+            }
+            _ => {}
+        }
+    }
+    unsafe { MEMORY_USAGE = (used, total); }
+}
+
+/// Used and total bytes of RAM. Without a heap this only changes at boot.
+pub fn usage() -> (u64, u64) {
+    unsafe { MEMORY_USAGE }
+}
+
+/// Virtual address at which the bootloader mapped the given physical address.
+pub fn phys_to_virt(phys: u64) -> *mut u8 {
+    (unsafe { PHYSICAL_MEMORY_OFFSET } + phys) as *mut u8
+}
+
+fn read_cr3() -> u64 {
+    let value: u64;
+    unsafe { core::arch::asm!("mov {}, cr3", out(reg) value, options(nomem, nostack, preserves_flags)); }
+    value
+}
+
+fn read_entry(table_phys: u64, index: u64) -> u64 {
+    let offset = unsafe { PHYSICAL_MEMORY_OFFSET };
+    let entry = (offset + table_phys + index * 8) as *const u64;
+    unsafe { core::ptr::read_volatile(entry) }
+}
+
+pub fn virt_to_phys(addr: u64) -> Option<u64> {
+    const PRESENT: u64 = 1;
+    const HUGE_PAGE: u64 = 1 << 7;
+    const ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+    let indexes = [
+        (addr >> 39) & 0x1FF,
+        (addr >> 30) & 0x1FF,
+        (addr >> 21) & 0x1FF,
+        (addr >> 12) & 0x1FF,
+    ];
+
+    let mut table = read_cr3() & ADDR_MASK;
+    for (level, &index) in indexes.iter().enumerate() {
+        let entry = read_entry(table, index);
+        if entry & PRESENT == 0 {
+            return None;
+        }
+        if entry & HUGE_PAGE != 0 {
+            match level {
+                1 => return Some((entry & 0x000F_FFFF_C000_0000) + (addr & 0x3FFF_FFFF)),
+                2 => return Some((entry & 0x000F_FFFF_FFE0_0000) + (addr & 0x1F_FFFF)),
+                _ => return None,
+            }
+        }
+        table = entry & ADDR_MASK;
+    }
+    Some(table + (addr & (PAGE_SIZE - 1)))
+}
+
+pub fn dma_address(buffer: &[u8]) -> Result<u32, &'static str> {
+    let start = buffer.as_ptr() as u64;
+    let phys_start = virt_to_phys(start).ok_or("Buffer is not mapped")?;
+
+    let mut page = start & !(PAGE_SIZE - 1);
+    let end = start + buffer.len() as u64;
+    while page < end {
+        let expected = phys_start + page.max(start) - start;
+        match virt_to_phys(page.max(start)) {
+            Some(phys) if phys == expected => {}
+            _ => return Err("Buffer is not physically contiguous"),
+        }
+        page += PAGE_SIZE;
+    }
+
+    if phys_start + buffer.len() as u64 > u32::MAX as u64 {
+        return Err("Buffer is above 4 GiB");
+    }
+    Ok(phys_start as u32)
+}
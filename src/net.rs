@@ -0,0 +1,739 @@
+#![allow(dead_code)]
+
+use crate::args;
+use crate::commands::{self, Command};
+use crate::io::{Io, Sink};
+use crate::rtl8139::Rtl8139;
+use crate::{timer, vga_buffer};
+
+pub type Ipv4Addr = [u8; 4];
+pub type MacAddr = [u8; 6];
+
+pub const BROADCAST_MAC: MacAddr = [0xFF; 6];
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const IP_PROTO_ICMP: u8 = 1;
+const IP_PROTO_UDP: u8 = 17;
+
+const ETH_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const ICMP_HEADER_LEN: usize = 8;
+const MAX_FRAME_LEN: usize = 1514;
+
+pub const MAX_UDP_PAYLOAD: usize = MAX_FRAME_LEN - ETH_HEADER_LEN - IPV4_HEADER_LEN - UDP_HEADER_LEN;
+
+const ARP_CACHE_SIZE: usize = 16;
+const UDP_SOCKETS: usize = 8;
+const UDP_QUEUE_LEN: usize = 4;
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+const ICMP_ECHO_ID: u16 = 0xA0A0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NetConfig {
+    pub ip: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+}
+
+impl NetConfig {
+    pub const fn qemu_user() -> Self {
+        Self {
+            ip: [10, 0, 2, 15],
+            netmask: [255, 255, 255, 0],
+            gateway: [10, 0, 2, 2],
+        }
+    }
+}
+
+struct Interface {
+    nic: Rtl8139,
+    config: NetConfig,
+    packets_sent: usize,
+    packets_received: usize,
+}
+
+#[derive(Clone, Copy)]
+struct ArpEntry {
+    ip: Ipv4Addr,
+    mac: MacAddr,
+    valid: bool,
+}
+
+#[derive(Clone, Copy)]
+struct UdpDatagram {
+    src_ip: Ipv4Addr,
+    src_port: u16,
+    data: [u8; MAX_UDP_PAYLOAD],
+    len: usize,
+}
+
+#[derive(Clone, Copy)]
+struct UdpSocket {
+    port: u16,
+    bound: bool,
+    queue: [UdpDatagram; UDP_QUEUE_LEN],
+    head: usize,
+    count: usize,
+}
+
+const EMPTY_DATAGRAM: UdpDatagram = UdpDatagram {
+    src_ip: [0; 4],
+    src_port: 0,
+    data: [0; MAX_UDP_PAYLOAD],
+    len: 0,
+};
+
+const EMPTY_SOCKET: UdpSocket = UdpSocket {
+    port: 0,
+    bound: false,
+    queue: [EMPTY_DATAGRAM; UDP_QUEUE_LEN],
+    head: 0,
+    count: 0,
+};
+
+#[derive(Clone, Copy)]
+struct EchoReply {
+    sequence: u16,
+    ttl: u8,
+    received_at: u64,
+}
+
+static mut INTERFACE: Option<Interface> = None;
+static mut ARP_CACHE: [ArpEntry; ARP_CACHE_SIZE] = [ArpEntry { ip: [0; 4], mac: [0; 6], valid: false }; ARP_CACHE_SIZE];
+static mut ARP_NEXT: usize = 0;
+static mut UDP_TABLE: [UdpSocket; UDP_SOCKETS] = [EMPTY_SOCKET; UDP_SOCKETS];
+static mut NEXT_EPHEMERAL_PORT: u16 = EPHEMERAL_PORT_START;
+static mut IP_IDENTIFICATION: u16 = 1;
+static mut LAST_ECHO_REPLY: Option<EchoReply> = None;
+
+fn interface() -> Option<&'static mut Interface> {
+    unsafe { (*(&raw mut INTERFACE)).as_mut() }
+}
+
+fn arp_cache() -> &'static mut [ArpEntry; ARP_CACHE_SIZE] {
+    unsafe { &mut *(&raw mut ARP_CACHE) }
+}
+
+fn udp_table() -> &'static mut [UdpSocket; UDP_SOCKETS] {
+    unsafe { &mut *(&raw mut UDP_TABLE) }
+}
+
+pub fn init() -> Result<(), &'static str> {
+    let nic = Rtl8139::init()?;
+    unsafe {
+        INTERFACE = Some(Interface {
+            nic,
+            config: NetConfig::qemu_user(),
+            packets_sent: 0,
+            packets_received: 0,
+        });
+    }
+    Ok(())
+}
+
+pub fn is_up() -> bool {
+    interface().is_some()
+}
+
+pub fn config() -> Option<NetConfig> {
+    interface().map(|iface| iface.config)
+}
+
+pub fn set_config(config: NetConfig) -> Result<(), &'static str> {
+    let iface = interface().ok_or("Network is down")?;
+    iface.config = config;
+    Ok(())
+}
+
+pub fn mac_address() -> Option<MacAddr> {
+    interface().map(|iface| iface.nic.mac())
+}
+
+pub fn packet_counts() -> (usize, usize) {
+    interface().map(|iface| (iface.packets_sent, iface.packets_received)).unwrap_or((0, 0))
+}
+
+pub fn arp_entries() -> [Option<(Ipv4Addr, MacAddr)>; ARP_CACHE_SIZE] {
+    let mut result = [None; ARP_CACHE_SIZE];
+    for (i, entry) in arp_cache().iter().enumerate() {
+        if entry.valid {
+            result[i] = Some((entry.ip, entry.mac));
+        }
+    }
+    result
+}
+
+fn arp_lookup(ip: Ipv4Addr) -> Option<MacAddr> {
+    arp_cache().iter().find(|e| e.valid && e.ip == ip).map(|e| e.mac)
+}
+
+fn arp_insert(ip: Ipv4Addr, mac: MacAddr) {
+    let cache = arp_cache();
+    if let Some(entry) = cache.iter_mut().find(|e| e.valid && e.ip == ip) {
+        entry.mac = mac;
+        return;
+    }
+    let slot = match cache.iter().position(|e| !e.valid) {
+        Some(index) => index,
+        None => unsafe {
+            let index = ARP_NEXT;
+            ARP_NEXT = (ARP_NEXT + 1) % ARP_CACHE_SIZE;
+            index
+        },
+    };
+    cache[slot] = ArpEntry { ip, mac, valid: true };
+}
+
+pub fn parse_ip(s: &str) -> Option<Ipv4Addr> {
+    let mut ip = [0u8; 4];
+    let mut count = 0;
+    for part in s.trim().split('.') {
+        if count >= 4 || part.is_empty() || part.len() > 3 {
+            return None;
+        }
+        let mut value = 0u16;
+        for c in part.bytes() {
+            if !c.is_ascii_digit() {
+                return None;
+            }
+            value = value * 10 + (c - b'0') as u16;
+        }
+        if value > 255 {
+            return None;
+        }
+        ip[count] = value as u8;
+        count += 1;
+    }
+    if count == 4 { Some(ip) } else { None }
+}
+
+pub fn write_ip(writer: &mut dyn Sink, ip: Ipv4Addr) {
+    write!(writer, "{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]);
+}
+
+pub fn write_mac(writer: &mut dyn Sink, mac: MacAddr) {
+    write!(writer, "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}", mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]);
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn same_subnet(config: &NetConfig, ip: Ipv4Addr) -> bool {
+    (0..4).all(|i| ip[i] & config.netmask[i] == config.ip[i] & config.netmask[i])
+}
+
+fn send_ethernet(dst: MacAddr, ethertype: u16, payload: &[u8]) -> Result<(), &'static str> {
+    let iface = interface().ok_or("Network is down")?;
+    if payload.len() + ETH_HEADER_LEN > MAX_FRAME_LEN {
+        return Err("Packet too large");
+    }
+
+    let mut frame = [0u8; MAX_FRAME_LEN];
+    frame[0..6].copy_from_slice(&dst);
+    frame[6..12].copy_from_slice(&iface.nic.mac());
+    frame[12..14].copy_from_slice(&ethertype.to_be_bytes());
+    frame[ETH_HEADER_LEN..ETH_HEADER_LEN + payload.len()].copy_from_slice(payload);
+
+    iface.nic.send(&frame[..ETH_HEADER_LEN + payload.len()])?;
+    iface.packets_sent += 1;
+    Ok(())
+}
+
+fn send_arp(oper: u16, target_mac: MacAddr, target_ip: Ipv4Addr, dst: MacAddr) -> Result<(), &'static str> {
+    let iface = interface().ok_or("Network is down")?;
+    let mut packet = [0u8; 28];
+    packet[0..2].copy_from_slice(&1u16.to_be_bytes());
+    packet[2..4].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+    packet[4] = 6;
+    packet[5] = 4;
+    packet[6..8].copy_from_slice(&oper.to_be_bytes());
+    packet[8..14].copy_from_slice(&iface.nic.mac());
+    packet[14..18].copy_from_slice(&iface.config.ip);
+    packet[18..24].copy_from_slice(&target_mac);
+    packet[24..28].copy_from_slice(&target_ip);
+    send_ethernet(dst, ETHERTYPE_ARP, &packet)
+}
+
+pub fn resolve(ip: Ipv4Addr, timeout_ms: u64) -> Result<MacAddr, &'static str> {
+    let config = config().ok_or("Network is down")?;
+    if ip == [255, 255, 255, 255] {
+        return Ok(BROADCAST_MAC);
+    }
+    let next_hop = if same_subnet(&config, ip) { ip } else { config.gateway };
+
+    if let Some(mac) = arp_lookup(next_hop) {
+        return Ok(mac);
+    }
+
+    let start = timer::uptime_ms();
+    let mut last_request = None;
+    while timer::uptime_ms() - start < timeout_ms {
+        if last_request.is_none_or(|t| timer::uptime_ms() - t >= 500) {
+            send_arp(1, [0; 6], next_hop, BROADCAST_MAC)?;
+            last_request = Some(timer::uptime_ms());
+        }
+        poll();
+        if let Some(mac) = arp_lookup(next_hop) {
+            return Ok(mac);
+        }
+    }
+    Err("ARP request timed out")
+}
+
+fn send_ipv4(dst_ip: Ipv4Addr, protocol: u8, payload: &[u8]) -> Result<(), &'static str> {
+    let config = config().ok_or("Network is down")?;
+    if payload.len() + IPV4_HEADER_LEN + ETH_HEADER_LEN > MAX_FRAME_LEN {
+        return Err("Packet too large");
+    }
+    let dst_mac = resolve(dst_ip, 2000)?;
+
+    let total_len = IPV4_HEADER_LEN + payload.len();
+    let mut packet = [0u8; MAX_FRAME_LEN - ETH_HEADER_LEN];
+    packet[0] = 0x45;
+    packet[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+    let id = unsafe {
+        let id = IP_IDENTIFICATION;
+        IP_IDENTIFICATION = IP_IDENTIFICATION.wrapping_add(1);
+        id
+    };
+    packet[4..6].copy_from_slice(&id.to_be_bytes());
+    packet[6] = 0x40;
+    packet[8] = 64;
+    packet[9] = protocol;
+    packet[12..16].copy_from_slice(&config.ip);
+    packet[16..20].copy_from_slice(&dst_ip);
+    let sum = checksum(&packet[..IPV4_HEADER_LEN]);
+    packet[10..12].copy_from_slice(&sum.to_be_bytes());
+    packet[IPV4_HEADER_LEN..total_len].copy_from_slice(payload);
+
+    send_ethernet(dst_mac, ETHERTYPE_IPV4, &packet[..total_len])
+}
+
+fn send_icmp_echo(dst_ip: Ipv4Addr, icmp_type: u8, id: u16, sequence: u16, data: &[u8]) -> Result<(), &'static str> {
+    let mut packet = [0u8; MAX_FRAME_LEN - ETH_HEADER_LEN - IPV4_HEADER_LEN];
+    let len = ICMP_HEADER_LEN + data.len();
+    if len > packet.len() {
+        return Err("Packet too large");
+    }
+    packet[0] = icmp_type;
+    packet[4..6].copy_from_slice(&id.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[ICMP_HEADER_LEN..len].copy_from_slice(data);
+    let sum = checksum(&packet[..len]);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    send_ipv4(dst_ip, IP_PROTO_ICMP, &packet[..len])
+}
+
+pub fn ping(dst_ip: Ipv4Addr, sequence: u16, timeout_ms: u64) -> Result<(u64, u8), &'static str> {
+    let mut payload = [0u8; 32];
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte = b'a' + (i % 23) as u8;
+    }
+
+    unsafe { LAST_ECHO_REPLY = None; }
+    let start = timer::uptime_ms();
+    send_icmp_echo(dst_ip, 8, ICMP_ECHO_ID, sequence, &payload)?;
+
+    while timer::uptime_ms() - start < timeout_ms {
+        poll();
+        if let Some(reply) = unsafe { LAST_ECHO_REPLY }.filter(|reply| reply.sequence == sequence) {
+            return Ok((reply.received_at - start, reply.ttl));
+        }
+    }
+    Err("Request timed out")
+}
+
+pub fn udp_bind(port: u16) -> Result<usize, &'static str> {
+    let table = udp_table();
+    let port = if port == 0 {
+        loop {
+            let candidate = unsafe {
+                let candidate = NEXT_EPHEMERAL_PORT;
+                NEXT_EPHEMERAL_PORT = if NEXT_EPHEMERAL_PORT == u16::MAX {
+                    EPHEMERAL_PORT_START
+                } else {
+                    NEXT_EPHEMERAL_PORT + 1
+                };
+                candidate
+            };
+            if !table.iter().any(|s| s.bound && s.port == candidate) {
+                break candidate;
+            }
+        }
+    } else {
+        if table.iter().any(|s| s.bound && s.port == port) {
+            return Err("Port already in use");
+        }
+        port
+    };
+
+    let index = table.iter().position(|s| !s.bound).ok_or("No free UDP sockets")?;
+    let socket = &mut table[index];
+    socket.port = port;
+    socket.bound = true;
+    socket.head = 0;
+    socket.count = 0;
+    Ok(index)
+}
+
+pub fn udp_close(handle: usize) {
+    if let Some(socket) = udp_table().get_mut(handle) {
+        socket.bound = false;
+        socket.count = 0;
+    }
+}
+
+pub fn udp_local_port(handle: usize) -> u16 {
+    udp_table().get(handle).map(|s| s.port).unwrap_or(0)
+}
+
+pub fn udp_send_to(handle: usize, dst_ip: Ipv4Addr, dst_port: u16, data: &[u8]) -> Result<(), &'static str> {
+    let socket = udp_table().get(handle).filter(|s| s.bound).ok_or("Invalid UDP socket")?;
+    if data.len() > MAX_UDP_PAYLOAD {
+        return Err("Datagram too large");
+    }
+
+    let len = UDP_HEADER_LEN + data.len();
+    let mut packet = [0u8; UDP_HEADER_LEN + MAX_UDP_PAYLOAD];
+    packet[0..2].copy_from_slice(&socket.port.to_be_bytes());
+    packet[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    packet[4..6].copy_from_slice(&(len as u16).to_be_bytes());
+    packet[UDP_HEADER_LEN..len].copy_from_slice(data);
+    send_ipv4(dst_ip, IP_PROTO_UDP, &packet[..len])
+}
+
+pub fn udp_recv_from(handle: usize, buf: &mut [u8], timeout_ms: u64) -> Result<(usize, Ipv4Addr, u16), &'static str> {
+    if udp_table().get(handle).filter(|s| s.bound).is_none() {
+        return Err("Invalid UDP socket");
+    }
+
+    let start = timer::uptime_ms();
+    loop {
+        poll();
+        let socket = &mut udp_table()[handle];
+        if socket.count > 0 {
+            let datagram = &socket.queue[socket.head];
+            let len = datagram.len.min(buf.len());
+            buf[..len].copy_from_slice(&datagram.data[..len]);
+            let result = (len, datagram.src_ip, datagram.src_port);
+            socket.head = (socket.head + 1) % UDP_QUEUE_LEN;
+            socket.count -= 1;
+            return Ok(result);
+        }
+        if timer::uptime_ms() - start >= timeout_ms {
+            return Err("Receive timed out");
+        }
+    }
+}
+
+pub fn poll() {
+    let mut frame = [0u8; MAX_FRAME_LEN];
+    loop {
+        let len = match interface() {
+            Some(iface) => match iface.nic.receive(&mut frame) {
+                Some(len) => {
+                    iface.packets_received += 1;
+                    len
+                }
+                None => return,
+            },
+            None => return,
+        };
+        if len >= ETH_HEADER_LEN {
+            handle_frame(&frame[..len]);
+        }
+    }
+}
+
+fn handle_frame(frame: &[u8]) {
+    let ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+    let payload = &frame[ETH_HEADER_LEN..];
+    match ethertype {
+        ETHERTYPE_ARP => handle_arp(payload),
+        ETHERTYPE_IPV4 => handle_ipv4(payload),
+        _ => {}
+    }
+}
+
+fn handle_arp(packet: &[u8]) {
+    if packet.len() < 28 || packet[4] != 6 || packet[5] != 4 {
+        return;
+    }
+    let config = match config() {
+        Some(config) => config,
+        None => return,
+    };
+
+    let oper = u16::from_be_bytes([packet[6], packet[7]]);
+    let mut sender_mac = [0u8; 6];
+    sender_mac.copy_from_slice(&packet[8..14]);
+    let mut sender_ip = [0u8; 4];
+    sender_ip.copy_from_slice(&packet[14..18]);
+    let target_ip = &packet[24..28];
+
+    if target_ip == config.ip || arp_lookup(sender_ip).is_some() {
+        arp_insert(sender_ip, sender_mac);
+    }
+
+    if oper == 1 && target_ip == config.ip {
+        let _ = send_arp(2, sender_mac, sender_ip, sender_mac);
+    }
+}
+
+fn handle_ipv4(packet: &[u8]) {
+    if packet.len() < IPV4_HEADER_LEN || packet[0] >> 4 != 4 {
+        return;
+    }
+    let header_len = ((packet[0] & 0x0F) as usize) * 4;
+    let total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if header_len < IPV4_HEADER_LEN || total_len < header_len || total_len > packet.len() {
+        return;
+    }
+    if checksum(&packet[..header_len]) != 0 {
+        return;
+    }
+    let fragment = u16::from_be_bytes([packet[6], packet[7]]);
+    if fragment & 0x3FFF != 0 {
+        return;
+    }
+
+    let config = match config() {
+        Some(config) => config,
+        None => return,
+    };
+    let dst = &packet[16..20];
+    if dst != config.ip && dst != [255, 255, 255, 255] {
+        return;
+    }
+
+    let mut src = [0u8; 4];
+    src.copy_from_slice(&packet[12..16]);
+    let ttl = packet[8];
+    let payload = &packet[header_len..total_len];
+
+    match packet[9] {
+        IP_PROTO_ICMP => handle_icmp(src, ttl, payload),
+        IP_PROTO_UDP => handle_udp(src, payload),
+        _ => {}
+    }
+}
+
+fn handle_icmp(src: Ipv4Addr, ttl: u8, packet: &[u8]) {
+    if packet.len() < ICMP_HEADER_LEN || checksum(packet) != 0 {
+        return;
+    }
+    let id = u16::from_be_bytes([packet[4], packet[5]]);
+    let sequence = u16::from_be_bytes([packet[6], packet[7]]);
+
+    match packet[0] {
+        8 => {
+            let _ = send_icmp_echo(src, 0, id, sequence, &packet[ICMP_HEADER_LEN..]);
+        }
+        0 if id == ICMP_ECHO_ID => unsafe {
+            LAST_ECHO_REPLY = Some(EchoReply {
+                sequence,
+                ttl,
+                received_at: timer::uptime_ms(),
+            });
+        },
+        _ => {}
+    }
+}
+
+fn handle_udp(src: Ipv4Addr, packet: &[u8]) {
+    if packet.len() < UDP_HEADER_LEN {
+        return;
+    }
+    let src_port = u16::from_be_bytes([packet[0], packet[1]]);
+    let dst_port = u16::from_be_bytes([packet[2], packet[3]]);
+    let len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    if len < UDP_HEADER_LEN || len > packet.len() {
+        return;
+    }
+    let data = &packet[UDP_HEADER_LEN..len];
+
+    let socket = match udp_table().iter_mut().find(|s| s.bound && s.port == dst_port) {
+        Some(socket) => socket,
+        None => return,
+    };
+    if socket.count >= UDP_QUEUE_LEN || data.len() > MAX_UDP_PAYLOAD {
+        return;
+    }
+
+    let slot = (socket.head + socket.count) % UDP_QUEUE_LEN;
+    let datagram = &mut socket.queue[slot];
+    datagram.src_ip = src;
+    datagram.src_port = src_port;
+    datagram.data[..data.len()].copy_from_slice(data);
+    datagram.len = data.len();
+    socket.count += 1;
+}
+
+fn cmd_ifconfig(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut config = match config() {
+        Some(config) => config,
+        None => {
+            io.write_string("Network is down (no RTL8139 NIC found).\n");
+            return 1;
+        }
+    };
+
+    if argv.len() > 1 {
+        if argv.len() > 3 {
+            io.write_string("Usage: ifconfig [ip] [gateway]\n");
+            return 2;
+        }
+        match parse_ip(argv[1]) {
+            Some(ip) => config.ip = ip,
+            None => {
+                io.write_string("Usage: ifconfig [ip] [gateway]\n");
+                return 2;
+            }
+        }
+        if let Some(&gateway) = argv.get(2) {
+            match parse_ip(gateway) {
+                Some(gateway) => config.gateway = gateway,
+                None => {
+                    io.write_string("Usage: ifconfig [ip] [gateway]\n");
+                    return 2;
+                }
+            }
+        }
+        let _ = set_config(config);
+    }
+
+    let (sent, received) = packet_counts();
+
+    io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::LightCyan, vga_buffer::Color::Black));
+    io.write_string("eth0");
+    io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
+    io.write_string(" (RTL8139)\n");
+    io.write_string("  MAC:      ");
+    if let Some(mac) = mac_address() {
+        write_mac(io, mac);
+    }
+    io.write_string("\n  IP:       ");
+    write_ip(io, config.ip);
+    io.write_string("\n  Netmask:  ");
+    write_ip(io, config.netmask);
+    io.write_string("\n  Gateway:  ");
+    write_ip(io, config.gateway);
+    writeln!(io, "\n  TX packets: {}  RX packets: {}", sent, received);
+    0
+}
+
+fn cmd_ping(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    let count = match flags.value("count").map(args::parse_number) {
+        None => Some(4),
+        Some(Some(count)) if count > 0 && count <= u16::MAX as usize => Some(count as u16),
+        Some(_) => None,
+    };
+    let target = match (flags.check("", &["count"]), flags.positional(), count) {
+        (Ok(_), &[ip], Some(count)) => parse_ip(ip).map(|ip| (ip, count)),
+        _ => None,
+    };
+    let (target, count) = match target {
+        Some(target) => target,
+        None => {
+            io.write_string("Usage: ping [--count=N] <ip>\n");
+            return 2;
+        }
+    };
+    if !is_up() {
+        io.write_string("Network is down (no RTL8139 NIC found).\n");
+        return 1;
+    }
+
+    let mut received = 0;
+
+    io.write_string("PING ");
+    write_ip(io, target);
+    io.write_string("\n");
+
+    for seq in 1..=count {
+        match ping(target, seq, 1000) {
+            Ok((rtt, ttl)) => {
+                received += 1;
+                io.write_string("Reply from ");
+                write_ip(io, target);
+                writeln!(io, ": seq={} ttl={} time={}ms", seq, ttl, rtt);
+                timer::sleep_ms(1000 - rtt.min(1000));
+            }
+            Err(e) => {
+                io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black));
+                writeln!(io, "seq={}: {}", seq, e);
+                io.set_color(vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black));
+            }
+        }
+    }
+
+    writeln!(io, "{} packets sent, {} received", count, received);
+    if received == 0 { 1 } else { 0 }
+}
+
+fn cmd_arp(io: &mut Io, _argv: &[&str]) -> i32 {
+    if !is_up() {
+        io.write_string("Network is down (no RTL8139 NIC found).\n");
+        return 1;
+    }
+
+    io.write_string("Address          HWaddress\n");
+    let mut count = 0;
+    for entry in arp_entries().iter().flatten() {
+        let (ip, mac) = *entry;
+        let start = io.column();
+        write_ip(io, ip);
+        while io.column() < start + 17 {
+            io.write_byte(b' ');
+        }
+        write_mac(io, mac);
+        io.write_byte(b'\n');
+        count += 1;
+    }
+    if count == 0 {
+        io.write_string("(ARP cache is empty)\n");
+    }
+    0
+}
+
+pub fn register_commands() {
+    commands::register_all(&[
+        Command {
+            name: "ifconfig",
+            aliases: &[],
+            usage: "ifconfig [ip] [gateway]",
+            summary: "Show or set the network configuration",
+            handler: cmd_ifconfig,
+        },
+        Command {
+            name: "ping",
+            aliases: &[],
+            usage: "ping [--count=N] <ip>",
+            summary: "Send ICMP echo requests to a host",
+            handler: cmd_ping,
+        },
+        Command {
+            name: "arp",
+            aliases: &[],
+            usage: "arp",
+            summary: "Show the ARP cache",
+            handler: cmd_arp,
+        },
+    ]);
+}
@@ -0,0 +1,353 @@
+use crate::ansi::{self, Action};
+use crate::commands::{self, Command};
+use crate::cp437;
+use crate::file_system;
+use crate::io::{Io, Sink, TextBuffer};
+use crate::vga_buffer::{self, Color, ColorCode, ScreenChar, Writer, MAX_WIDTH};
+use crate::keyboard::{self, Key};
+use crate::line_editor;
+
+const PAGER_SIZE: usize = 16384;
+const MAX_LINES: usize = 2048;
+const QUERY_LEN: usize = 64;
+// Shown at the end when the output didn't fit; `store` keeps room for it and a newline.
+const TRUNCATED: &[u8] = b"[output truncated]\n";
+
+const TEXT_COLOR: ColorCode = ColorCode::new(Color::White, Color::Black);
+const STATUS_COLOR: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+const MATCH_COLOR: ColorCode = ColorCode::new(Color::Black, Color::Yellow);
+
+struct PagerBuffer {
+    text: [u8; PAGER_SIZE],
+    colors: [ColorCode; PAGER_SIZE],
+    len: usize,
+    color: ColorCode,
+    column: usize,
+    starts: [usize; MAX_LINES],
+    lines: usize,
+    ansi: ansi::Parser,
+    utf8: cp437::Utf8Decoder,
+    truncated: bool,
+}
+
+static mut PAGER_BUFFER: PagerBuffer = PagerBuffer {
+    text: [0; PAGER_SIZE],
+    colors: [TEXT_COLOR; PAGER_SIZE],
+    len: 0,
+    color: TEXT_COLOR,
+    column: 0,
+    starts: [0; MAX_LINES],
+    lines: 0,
+    ansi: ansi::Parser::new(),
+    utf8: cp437::Utf8Decoder::new(),
+    truncated: false,
+};
+
+fn pager_buffer() -> &'static mut PagerBuffer {
+    unsafe { &mut *(&raw mut PAGER_BUFFER) }
+}
+
+// The last row holds the status line.
+fn page_rows() -> usize {
+    vga_buffer::height() - 1
+}
+
+impl PagerBuffer {
+    fn reset(&mut self, color: ColorCode) {
+        self.len = 0;
+        self.color = color;
+        self.column = 0;
+        self.starts[0] = 0;
+        self.lines = 0;
+        self.ansi.reset();
+        self.utf8 = cp437::Utf8Decoder::new();
+        self.truncated = false;
+    }
+
+    fn screen_lines(&self) -> usize {
+        self.lines + if self.len > 0 && self.text[self.len - 1] != b'\n' { 1 } else { 0 }
+    }
+
+    fn line(&self, index: usize) -> &[u8] {
+        let start = self.starts[index];
+        let end = if index + 1 <= self.lines && index + 1 < MAX_LINES { self.starts[index + 1] } else { self.len };
+        let line = &self.text[start..end.max(start)];
+        line.strip_suffix(b"\n").unwrap_or(line)
+    }
+
+    fn end_line(&mut self) {
+        if self.lines + 1 < MAX_LINES {
+            self.lines += 1;
+            self.starts[self.lines] = self.len;
+        }
+        self.column = 0;
+    }
+
+    fn store(&mut self, byte: u8) {
+        if self.len + 1 + TRUNCATED.len() >= PAGER_SIZE || self.lines + 3 >= MAX_LINES {
+            self.truncated = true;
+            return;
+        }
+        self.push(byte);
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len >= PAGER_SIZE {
+            return;
+        }
+        if byte != b'\n' && self.column >= vga_buffer::width() {
+            self.end_line();
+        }
+        self.text[self.len] = byte;
+        self.colors[self.len] = self.color;
+        self.len += 1;
+        if byte == b'\n' {
+            self.end_line();
+        } else {
+            self.column += 1;
+        }
+    }
+
+    /// Ends the output, marking it when some of it was dropped.
+    fn finish(&mut self) {
+        if !self.truncated {
+            return;
+        }
+        if self.column > 0 {
+            self.push(b'\n');
+        }
+        self.color = STATUS_COLOR;
+        for &byte in TRUNCATED {
+            self.push(byte);
+        }
+    }
+}
+
+impl Sink for PagerBuffer {
+    fn write_byte(&mut self, byte: u8) {
+        match self.ansi.feed(byte) {
+            Action::Print(byte) => {
+                if let Some(glyph) = self.utf8.feed_glyph(byte) {
+                    self.store(glyph);
+                }
+            }
+            Action::Csi(csi) if csi.command == b'm' && !csi.private => {
+                self.color = self.ansi.apply_sgr(&csi, self.color);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_color(&mut self, color: ColorCode) {
+        self.color = color;
+    }
+
+    fn column(&self) -> usize {
+        self.column
+    }
+}
+
+fn put(writer: &mut Writer, row: usize, col: usize, byte: u8, color: ColorCode) {
+    writer.cell(row, col).write(ScreenChar { ascii_character: byte, color_code: color });
+}
+
+fn find_match(line: &[u8], query: &[u8], from: usize) -> Option<usize> {
+    if query.is_empty() || line.len() < query.len() {
+        return None;
+    }
+    (from..=line.len() - query.len()).find(|&i| line[i..i + query.len()].eq_ignore_ascii_case(query))
+}
+
+fn render(writer: &mut Writer, buffer: &PagerBuffer, top: usize, query: &[u8]) {
+    let total = buffer.screen_lines();
+    for row in 0..page_rows() {
+        let index = top + row;
+        let line: &[u8] = if index < total { buffer.line(index) } else { b"" };
+        let offset = if index < total { buffer.starts[index] } else { 0 };
+        let mut highlight_end = 0;
+        let mut next_match = find_match(line, query, 0);
+        for col in 0..vga_buffer::width() {
+            if let Some(start) = next_match.filter(|&start| start == col) {
+                highlight_end = start + query.len();
+                next_match = find_match(line, query, start + 1);
+            }
+            match line.get(col) {
+                Some(&byte) => {
+                    let byte = if byte == b'\t' { b' ' } else { byte };
+                    let color = if col < highlight_end { MATCH_COLOR } else { buffer.colors[offset + col] };
+                    put(writer, row, col, byte, color);
+                }
+                None if index >= total && col == 0 => put(writer, row, col, b'~', TEXT_COLOR),
+                None => put(writer, row, col, b' ', TEXT_COLOR),
+            }
+        }
+    }
+}
+
+fn render_status(writer: &mut Writer, top: usize, total: usize, message: &str) {
+    let mut text = TextBuffer::<MAX_WIDTH>::new();
+    let bottom = (top + page_rows()).min(total);
+    if bottom >= total {
+        text.write_string("(END)");
+    } else {
+        write!(text, "-- More -- ({}%)", bottom * 100 / total.max(1));
+    }
+    text.write_string("  Space:page Enter:line b:back /:search n:next q:quit ");
+    text.write_string(message);
+    let text = text.as_bytes();
+    for col in 0..vga_buffer::width() {
+        put(writer, page_rows(), col, text.get(col).copied().unwrap_or(b' '), STATUS_COLOR);
+    }
+}
+
+fn search(buffer: &PagerBuffer, query: &[u8], from: usize) -> Option<usize> {
+    (from..buffer.screen_lines()).find(|&index| find_match(buffer.line(index), query, 0).is_some())
+}
+
+fn read_query(writer: &mut Writer, query: &mut [u8; QUERY_LEN]) -> usize {
+    for col in 0..vga_buffer::width() {
+        put(writer, page_rows(), col, b' ', TEXT_COLOR);
+    }
+    writer.set_cursor_position(page_rows(), 0);
+    writer.color_code = TEXT_COLOR;
+    writer.write_byte(b'/');
+    writer.show_cursor();
+    let mut line = [0u8; QUERY_LEN];
+    let input = line_editor::read_line(writer, &mut line);
+    writer.disable_cursor();
+    cp437::decode_utf8(input.as_bytes(), query)
+}
+
+fn show(writer: &mut Writer, buffer: &PagerBuffer) {
+    let total = buffer.screen_lines();
+    let page_rows = page_rows();
+    let last_top = total.saturating_sub(page_rows);
+    let mut top = 0;
+    let mut query = [0u8; QUERY_LEN];
+    let mut query_len = 0;
+    let mut message = "";
+    writer.disable_cursor();
+
+    loop {
+        render(writer, buffer, top, &query[..query_len]);
+        render_status(writer, top, total, message);
+        message = "";
+
+        let key = keyboard::read_key().key;
+        match key {
+            Key::Char(' ') | Key::PageDown => {
+                if top >= last_top {
+                    break;
+                }
+                top = (top + page_rows).min(last_top);
+            }
+            Key::Enter | Key::Down => top = (top + 1).min(last_top),
+            Key::Up => top = top.saturating_sub(1),
+            Key::PageUp | Key::Char('b') => top = top.saturating_sub(page_rows),
+            Key::Home => top = 0,
+            Key::End => top = last_top,
+            Key::Escape | Key::Char('q') => break,
+            Key::Char('/') | Key::Char('n') => {
+                if key == Key::Char('/') {
+                    query_len = read_query(writer, &mut query);
+                }
+                match search(buffer, &query[..query_len], if key == Key::Char('n') { top + 1 } else { top }) {
+                    Some(index) => top = index.min(last_top),
+                    None => message = "Pattern not found",
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for col in 0..vga_buffer::width() {
+        put(writer, page_rows, col, b' ', TEXT_COLOR);
+    }
+    writer.set_cursor_position(page_rows, 0);
+    writer.color_code = TEXT_COLOR;
+    writer.show_cursor();
+}
+
+fn flush(writer: &mut Writer, buffer: &PagerBuffer) {
+    let color = writer.color_code;
+    for i in 0..buffer.len {
+        writer.color_code = buffer.colors[i];
+        writer.write_glyph(buffer.text[i]);
+    }
+    writer.color_code = color;
+}
+
+fn display(writer: &mut Writer, buffer: &PagerBuffer) {
+    if buffer.screen_lines() > page_rows() {
+        show(writer, buffer);
+    } else {
+        flush(writer, buffer);
+    }
+}
+
+pub fn paged<F: FnOnce(&mut Io) -> i32>(io: &mut Io, f: F) -> i32 {
+    if io.parts().1.is_some() {
+        return f(io);
+    }
+
+    let buffer = pager_buffer();
+    buffer.reset(io.console.color_code);
+    let status = {
+        let stdin = io.stdin.take();
+        let mut inner = Io::new(&mut *io.console, None, Some(buffer));
+        inner.stdin = stdin;
+        f(&mut inner)
+    };
+    let buffer = pager_buffer();
+    buffer.finish();
+    display(io.console, buffer);
+    status
+}
+
+fn cmd_more(io: &mut Io, argv: &[&str]) -> i32 {
+    paged(io, |io| {
+        if argv.len() < 2 {
+            return match io.stdin.as_mut().map(|stdin| stdin.read_all()) {
+                Some(data) => {
+                    for &byte in data {
+                        io.write_byte(byte);
+                    }
+                    0
+                }
+                None => {
+                    io.write_string("Usage: more <file>... (or pipe input into it)\n");
+                    2
+                }
+            };
+        }
+        let mut status = 0;
+        for &name in &argv[1..] {
+            let found = file_system::with_fs(|fs| match fs.read_file(name) {
+                Ok(data) => {
+                    for &byte in data {
+                        io.write_byte(byte);
+                    }
+                    true
+                }
+                Err(_) => false,
+            });
+            if !found {
+                io.write_string("more: No such file: ");
+                io.write_string(name);
+                io.write_string("\n");
+                status = 1;
+            }
+        }
+        status
+    })
+}
+
+pub fn register_commands() {
+    commands::register_all(&[Command {
+        name: "more",
+        aliases: &["less"],
+        usage: "more [file]...",
+        summary: "Page through text (Space, Enter, b, /search, q)",
+        handler: cmd_more,
+    }]);
+}
@@ -0,0 +1,72 @@
+use crate::port;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+fn config_address(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    0x8000_0000
+        | ((bus as u32) << 16)
+        | ((device as u32) << 11)
+        | ((function as u32) << 8)
+        | ((offset as u32) & 0xFC)
+}
+
+pub fn config_read(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    port::outl(CONFIG_ADDRESS, config_address(bus, device, function, offset));
+    port::inl(CONFIG_DATA)
+}
+
+pub fn config_write(bus: u8, device: u8, function: u8, offset: u8, value: u32) {
+    port::outl(CONFIG_ADDRESS, config_address(bus, device, function, offset));
+    port::outl(CONFIG_DATA, value);
+}
+
+impl PciDevice {
+    pub fn read(&self, offset: u8) -> u32 {
+        config_read(self.bus, self.device, self.function, offset)
+    }
+
+    pub fn write(&self, offset: u8, value: u32) {
+        config_write(self.bus, self.device, self.function, offset, value)
+    }
+
+    pub fn bar(&self, index: u8) -> u32 {
+        self.read(0x10 + index * 4)
+    }
+
+    pub fn enable_bus_mastering(&self) {
+        let command = self.read(0x04);
+        self.write(0x04, command | 0x0007);
+    }
+}
+
+pub fn find_device(vendor_id: u16, device_id: u16) -> Option<PciDevice> {
+    for bus in 0..=255u8 {
+        for device in 0..32u8 {
+            let id = config_read(bus, device, 0, 0x00);
+            if id & 0xFFFF == 0xFFFF {
+                continue;
+            }
+            let header_type = (config_read(bus, device, 0, 0x0C) >> 16) as u8;
+            let functions = if header_type & 0x80 != 0 { 8 } else { 1 };
+
+            for function in 0..functions {
+                let id = config_read(bus, device, function, 0x00);
+                if id & 0xFFFF == 0xFFFF {
+                    continue;
+                }
+                if id as u16 == vendor_id && (id >> 16) as u16 == device_id {
+                    return Some(PciDevice { bus, device, function });
+                }
+            }
+        }
+    }
+    None
+}
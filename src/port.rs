@@ -0,0 +1,23 @@
+pub fn inb(port: u16) -> u8 {
+    let value: u8;
+    unsafe { core::arch::asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack, preserves_flags)); }
+    value
+}
+
+pub fn outb(port: u16, value: u8) {
+    unsafe { core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags)); }
+}
+
+pub fn outw(port: u16, value: u16) {
+    unsafe { core::arch::asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags)); }
+}
+
+pub fn inl(port: u16) -> u32 {
+    let value: u32;
+    unsafe { core::arch::asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack, preserves_flags)); }
+    value
+}
+
+pub fn outl(port: u16, value: u32) {
+    unsafe { core::arch::asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags)); }
+}
@@ -0,0 +1,82 @@
+use crate::port;
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+const MODE_24_HOUR: u8 = 0x02;
+const MODE_BINARY: u8 = 0x04;
+const HOUR_PM: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+fn read_register(register: u8) -> u8 {
+    // Bit 7 of the address port masks NMIs, keep it clear.
+    port::outb(CMOS_ADDRESS, register & 0x7F);
+    port::inb(CMOS_DATA)
+}
+
+fn read_raw() -> DateTime {
+    while read_register(REG_STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    DateTime {
+        year: read_register(REG_YEAR) as u16,
+        month: read_register(REG_MONTH),
+        day: read_register(REG_DAY),
+        hour: read_register(REG_HOURS),
+        minute: read_register(REG_MINUTES),
+        second: read_register(REG_SECONDS),
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+/// Current time of the CMOS clock, which PCs usually keep in local time.
+pub fn now() -> DateTime {
+    // Read until two reads agree, so an update between registers doesn't tear the value.
+    let mut time = read_raw();
+    loop {
+        let again = read_raw();
+        if again == time {
+            break;
+        }
+        time = again;
+    }
+
+    let status = read_register(REG_STATUS_B);
+    let pm = time.hour & HOUR_PM != 0;
+    time.hour &= !HOUR_PM;
+    if status & MODE_BINARY == 0 {
+        time.second = from_bcd(time.second);
+        time.minute = from_bcd(time.minute);
+        time.hour = from_bcd(time.hour);
+        time.day = from_bcd(time.day);
+        time.month = from_bcd(time.month);
+        time.year = from_bcd(time.year as u8) as u16;
+    }
+    if status & MODE_24_HOUR == 0 {
+        time.hour = (time.hour % 12) + if pm { 12 } else { 0 };
+    }
+    time.year += 2000;
+    time
+}
@@ -0,0 +1,168 @@
+use core::sync::atomic::{compiler_fence, Ordering};
+use crate::{memory, pci, port};
+
+const VENDOR_ID: u16 = 0x10EC;
+const DEVICE_ID: u16 = 0x8139;
+
+const REG_MAC: u16 = 0x00;
+const REG_TSD0: u16 = 0x10;
+const REG_TSAD0: u16 = 0x20;
+const REG_RBSTART: u16 = 0x30;
+const REG_CR: u16 = 0x37;
+const REG_CAPR: u16 = 0x38;
+const REG_IMR: u16 = 0x3C;
+const REG_ISR: u16 = 0x3E;
+const REG_RCR: u16 = 0x44;
+const REG_CONFIG1: u16 = 0x52;
+
+const CR_RESET: u8 = 0x10;
+const CR_RX_ENABLE: u8 = 0x08;
+const CR_TX_ENABLE: u8 = 0x04;
+const CR_BUFFER_EMPTY: u8 = 0x01;
+
+// Accept all, physical match, multicast and broadcast frames; let packets run past the ring end.
+const RCR_CONFIG: u32 = 0x0F | (1 << 7);
+
+const TSD_OWN: u32 = 1 << 13;
+
+const RX_BUFFER_LEN: usize = 8192;
+const RX_BUFFER_SIZE: usize = RX_BUFFER_LEN + 16 + 1536;
+const TX_BUFFER_SIZE: usize = 1536;
+const TX_DESCRIPTORS: usize = 4;
+
+#[repr(C, align(4096))]
+struct RxBuffer([u8; RX_BUFFER_SIZE]);
+
+#[repr(C, align(4096))]
+struct TxBuffers([[u8; TX_BUFFER_SIZE]; TX_DESCRIPTORS]);
+
+static mut RX_BUFFER: RxBuffer = RxBuffer([0; RX_BUFFER_SIZE]);
+static mut TX_BUFFERS: TxBuffers = TxBuffers([[0; TX_BUFFER_SIZE]; TX_DESCRIPTORS]);
+
+pub struct Rtl8139 {
+    io_base: u16,
+    mac: [u8; 6],
+    rx_offset: usize,
+    tx_index: usize,
+    tx_phys: [u32; TX_DESCRIPTORS],
+}
+
+impl Rtl8139 {
+    pub fn init() -> Result<Self, &'static str> {
+        let device = pci::find_device(VENDOR_ID, DEVICE_ID).ok_or("No RTL8139 found on the PCI bus")?;
+        device.enable_bus_mastering();
+
+        let bar0 = device.bar(0);
+        if bar0 & 1 == 0 {
+            return Err("RTL8139 BAR0 is not an I/O port");
+        }
+        let io_base = (bar0 & !0x3) as u16;
+
+        let rx_phys = memory::dma_address(unsafe { &(*(&raw const RX_BUFFER)).0 })?;
+        let mut tx_phys = [0u32; TX_DESCRIPTORS];
+        for (i, phys) in tx_phys.iter_mut().enumerate() {
+            *phys = memory::dma_address(unsafe { &(*(&raw const TX_BUFFERS)).0[i] })?;
+        }
+
+        port::outb(io_base + REG_CONFIG1, 0x00);
+
+        port::outb(io_base + REG_CR, CR_RESET);
+        let mut spins = 0;
+        while port::inb(io_base + REG_CR) & CR_RESET != 0 {
+            spins += 1;
+            if spins > 1_000_000 {
+                return Err("RTL8139 reset timed out");
+            }
+        }
+
+        port::outl(io_base + REG_RBSTART, rx_phys);
+        port::outw(io_base + REG_IMR, 0x0000);
+        port::outl(io_base + REG_RCR, RCR_CONFIG);
+        port::outb(io_base + REG_CR, CR_RX_ENABLE | CR_TX_ENABLE);
+
+        let mut mac = [0u8; 6];
+        for (i, byte) in mac.iter_mut().enumerate() {
+            *byte = port::inb(io_base + REG_MAC + i as u16);
+        }
+
+        Ok(Self {
+            io_base,
+            mac,
+            rx_offset: 0,
+            tx_index: 0,
+            tx_phys,
+        })
+    }
+
+    pub fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), &'static str> {
+        if frame.len() > TX_BUFFER_SIZE {
+            return Err("Frame too large");
+        }
+
+        let index = self.tx_index;
+        let tsd = self.io_base + REG_TSD0 + (index as u16) * 4;
+        let tsad = self.io_base + REG_TSAD0 + (index as u16) * 4;
+
+        let mut spins = 0;
+        while port::inl(tsd) & TSD_OWN == 0 {
+            spins += 1;
+            if spins > 1_000_000 {
+                return Err("Transmit descriptor busy");
+            }
+        }
+
+        let buffer = unsafe { &mut (*(&raw mut TX_BUFFERS)).0[index] };
+        buffer[..frame.len()].copy_from_slice(frame);
+        let len = frame.len().max(60);
+        if len > frame.len() {
+            buffer[frame.len()..len].fill(0);
+        }
+
+        port::outl(tsad, self.tx_phys[index]);
+        port::outl(tsd, len as u32);
+
+        self.tx_index = (self.tx_index + 1) % TX_DESCRIPTORS;
+        Ok(())
+    }
+
+    pub fn receive(&mut self, out: &mut [u8]) -> Option<usize> {
+        if port::inb(self.io_base + REG_CR) & CR_BUFFER_EMPTY != 0 {
+            return None;
+        }
+        compiler_fence(Ordering::SeqCst);
+
+        let rx = unsafe { &(*(&raw const RX_BUFFER)).0 };
+        let offset = self.rx_offset;
+        let status = u16::from_le_bytes([rx[offset], rx[offset + 1]]);
+        let length = u16::from_le_bytes([rx[offset + 2], rx[offset + 3]]) as usize;
+
+        // A bad header means the length can't be trusted to find the next packet either.
+        if status & 0x01 == 0 || !(4..=1518 + 4).contains(&length) {
+            self.reset_receiver();
+            return Some(0);
+        }
+
+        let data_len = (length - 4).min(out.len());
+        out[..data_len].copy_from_slice(&rx[offset + 4..offset + 4 + data_len]);
+
+        self.rx_offset = ((offset + length + 4 + 3) & !3) % RX_BUFFER_LEN;
+        port::outw(self.io_base + REG_CAPR, (self.rx_offset as u16).wrapping_sub(16));
+        port::outw(self.io_base + REG_ISR, 0x0001);
+
+        Some(data_len)
+    }
+
+    /// Drops everything in the receive ring and starts again from its beginning.
+    fn reset_receiver(&mut self) {
+        port::outb(self.io_base + REG_CR, CR_TX_ENABLE);
+        port::outb(self.io_base + REG_CR, CR_RX_ENABLE | CR_TX_ENABLE);
+        port::outl(self.io_base + REG_RCR, RCR_CONFIG);
+        self.rx_offset = 0;
+        port::outw(self.io_base + REG_CAPR, 0u16.wrapping_sub(16));
+        port::outw(self.io_base + REG_ISR, 0x0001);
+    }
+}
@@ -0,0 +1,389 @@
+use crate::ansi;
+use crate::args;
+use crate::commands::{self, Command};
+use crate::file_system;
+use crate::io::{Io, Sink};
+use crate::shell;
+use crate::vga_buffer;
+
+pub const RC_FILE: &str = "/etc/rc";
+
+const MAX_LINES: usize = 128;
+const MAX_DEPTH: usize = 4;
+const MAX_ITERATIONS: usize = 1000;
+
+static mut DEPTH: usize = 0;
+
+enum Flow {
+    Continue,
+    Exit(i32),
+}
+
+type ScriptError = (&'static str, usize);
+
+fn split_keyword(line: &str) -> (&str, &str) {
+    match line.split_once([' ', '\t']) {
+        Some((keyword, rest)) => (keyword, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+fn strip_terminator<'a>(condition: &'a str, word: &str) -> &'a str {
+    match condition.strip_suffix(word).map(|c| c.trim_end()) {
+        Some(c) if c.ends_with(';') => c[..c.len() - 1].trim_end(),
+        _ => condition,
+    }
+}
+
+fn keyword(line: &str) -> &str {
+    split_keyword(shell::strip_comment(line).trim()).0
+}
+
+fn find_fi(lines: &[&str], start: usize, end: usize) -> Result<(Option<usize>, usize), ScriptError> {
+    let mut depth = 0;
+    let mut else_at = None;
+    for pc in start + 1..end {
+        match keyword(lines[pc]) {
+            "if" => depth += 1,
+            "else" if depth == 0 => {
+                if else_at.is_some() {
+                    return Err(("Duplicate 'else'", pc));
+                }
+                else_at = Some(pc);
+            }
+            "fi" if depth == 0 => return Ok((else_at, pc)),
+            "fi" => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(("'if' without matching 'fi'", start))
+}
+
+fn find_done(lines: &[&str], start: usize, end: usize) -> Result<usize, ScriptError> {
+    let mut depth = 0;
+    for pc in start + 1..end {
+        match keyword(lines[pc]) {
+            "while" => depth += 1,
+            "done" if depth == 0 => return Ok(pc),
+            "done" => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(("'while' without matching 'done'", start))
+}
+
+fn run_range(io: &mut Io, lines: &[&str], start: usize, end: usize) -> Result<Flow, ScriptError> {
+    let mut pc = start;
+    while pc < end {
+        let line = shell::strip_comment(lines[pc]).trim();
+        let (keyword, rest) = split_keyword(line);
+        match keyword {
+            "" | "then" | "do" => {}
+            "if" => {
+                let (else_at, fi_at) = find_fi(lines, pc, end)?;
+                let condition = strip_terminator(rest, "then");
+                let flow = if shell::execute(io, condition) == 0 {
+                    run_range(io, lines, pc + 1, else_at.unwrap_or(fi_at))?
+                } else if let Some(else_at) = else_at {
+                    run_range(io, lines, else_at + 1, fi_at)?
+                } else {
+                    Flow::Continue
+                };
+                if let Flow::Exit(status) = flow {
+                    return Ok(Flow::Exit(status));
+                }
+                pc = fi_at;
+            }
+            "while" => {
+                let done_at = find_done(lines, pc, end)?;
+                let condition = strip_terminator(rest, "do");
+                let mut iterations = 0;
+                while shell::execute(io, condition) == 0 {
+                    iterations += 1;
+                    if iterations > MAX_ITERATIONS {
+                        return Err(("Loop iteration limit reached", pc));
+                    }
+                    if let Flow::Exit(status) = run_range(io, lines, pc + 1, done_at)? {
+                        return Ok(Flow::Exit(status));
+                    }
+                }
+                pc = done_at;
+            }
+            "else" => return Err(("'else' without 'if'", pc)),
+            "fi" => return Err(("'fi' without 'if'", pc)),
+            "done" => return Err(("'done' without 'while'", pc)),
+            "exit" => {
+                let status = match rest {
+                    "" => shell::last_status(),
+                    code => match args::parse_number(code) {
+                        Some(code) => code as i32,
+                        None => return Err(("Invalid exit status", pc)),
+                    },
+                };
+                return Ok(Flow::Exit(status));
+            }
+            _ => {
+                shell::execute(io, line);
+            }
+        }
+        pc += 1;
+    }
+    Ok(Flow::Continue)
+}
+
+pub fn run(io: &mut Io, name: &str) -> Result<i32, &'static str> {
+    let mut content = [0u8; file_system::MAX_FILE_SIZE];
+    let len = file_system::with_fs(|fs| {
+        fs.read_file(name).map(|data| {
+            content[..data.len()].copy_from_slice(data);
+            data.len()
+        })
+    })
+    .map_err(|_| "Script not found")?;
+    let text = core::str::from_utf8(&content[..len]).map_err(|_| "Invalid UTF-8 in script")?;
+
+    let mut lines = [""; MAX_LINES];
+    let mut count = 0;
+    for line in text.lines() {
+        if count >= MAX_LINES {
+            return Err("Script has too many lines");
+        }
+        lines[count] = line;
+        count += 1;
+    }
+
+    unsafe {
+        if DEPTH >= MAX_DEPTH {
+            return Err("Scripts nested too deeply");
+        }
+        DEPTH += 1;
+    }
+    let result = run_range(io, &lines[..count], 0, count);
+    unsafe { DEPTH -= 1 };
+
+    match result {
+        Ok(Flow::Exit(status)) => Ok(status),
+        Ok(Flow::Continue) => Ok(shell::last_status()),
+        Err((message, line)) => {
+            io.console.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
+            writeln!(io.console, "{}: line {}: {}", name, line + 1, message);
+            io.console.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+            Ok(2)
+        }
+    }
+}
+
+pub fn run_startup(writer: &mut vga_buffer::Writer, name: &str) {
+    let exists = file_system::with_fs(|fs| fs.read_file(name).is_ok());
+    if exists {
+        let mut io = Io::new(writer, None, None);
+        let _ = run(&mut io, name);
+    }
+}
+
+fn cmd_source(io: &mut Io, argv: &[&str]) -> i32 {
+    let name = match argv.get(1) {
+        Some(&name) => name,
+        None => {
+            io.write_string("Usage: source <file>\n");
+            return 2;
+        }
+    };
+    match run(io, name) {
+        Ok(status) => status,
+        Err(e) => {
+            io.write_string(argv[0]);
+            io.write_string(": ");
+            io.write_string(e);
+            io.write_string(": ");
+            io.write_string(name);
+            io.write_string("\n");
+            1
+        }
+    }
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+fn write_escaped(io: &mut Io, text: &str) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 >= bytes.len() {
+            io.write_byte(bytes[i]);
+            i += 1;
+            continue;
+        }
+        i += 2;
+        match bytes[i - 1] {
+            b'n' => io.write_byte(b'\n'),
+            b't' => io.write_byte(b'\t'),
+            b'e' => io.write_byte(ansi::ESC),
+            b'\\' => io.write_byte(b'\\'),
+            b'0' => {
+                let mut value = 0u8;
+                let mut digits = 0;
+                while digits < 3 && i < bytes.len() && (b'0'..=b'7').contains(&bytes[i]) {
+                    value = value.wrapping_mul(8).wrapping_add(bytes[i] - b'0');
+                    digits += 1;
+                    i += 1;
+                }
+                io.write_byte(value);
+            }
+            b'x' => {
+                let mut value = 0u8;
+                let mut digits = 0;
+                while digits < 2 && i < bytes.len() {
+                    match hex_digit(bytes[i]) {
+                        Some(digit) => value = value * 16 + digit,
+                        None => break,
+                    }
+                    digits += 1;
+                    i += 1;
+                }
+                io.write_byte(value);
+            }
+            other => {
+                io.write_byte(b'\\');
+                io.write_byte(other);
+            }
+        }
+    }
+}
+
+fn cmd_echo(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = &argv[1..];
+    while let Some((&flag, rest)) = words.split_first() {
+        match flag {
+            "-n" => newline = false,
+            "-e" => escapes = true,
+            "-ne" | "-en" => {
+                newline = false;
+                escapes = true;
+            }
+            _ => break,
+        }
+        words = rest;
+    }
+
+    for (i, &word) in words.iter().enumerate() {
+        if i > 0 {
+            io.write_byte(b' ');
+        }
+        if escapes {
+            write_escaped(io, word);
+        } else {
+            io.write_string(word);
+        }
+    }
+    if newline {
+        io.write_byte(b'\n');
+    }
+    0
+}
+
+fn cmd_true(_io: &mut Io, _argv: &[&str]) -> i32 {
+    0
+}
+
+fn cmd_false(_io: &mut Io, _argv: &[&str]) -> i32 {
+    1
+}
+
+fn parse_int(s: &str) -> Option<i64> {
+    match s.strip_prefix('-') {
+        Some(digits) => args::parse_number(digits).map(|n| -(n as i64)),
+        None => args::parse_number(s).map(|n| n as i64),
+    }
+}
+
+fn evaluate(argv: &[&str]) -> Option<bool> {
+    match argv {
+        [] => Some(false),
+        ["!", rest @ ..] => evaluate(rest).map(|result| !result),
+        [value] => Some(!value.is_empty()),
+        ["-n", value] => Some(!value.is_empty()),
+        ["-z", value] => Some(value.is_empty()),
+        ["-e", name] | ["-f", name] => Some(file_system::with_fs(|fs| fs.read_file(name).is_ok())),
+        [a, "=", b] | [a, "==", b] => Some(a == b),
+        [a, "!=", b] => Some(a != b),
+        [a, op, b] => {
+            let (a, b) = (parse_int(a)?, parse_int(b)?);
+            match *op {
+                "-eq" => Some(a == b),
+                "-ne" => Some(a != b),
+                "-lt" => Some(a < b),
+                "-le" => Some(a <= b),
+                "-gt" => Some(a > b),
+                "-ge" => Some(a >= b),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn cmd_test(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut expression = &argv[1..];
+    if argv[0] == "[" {
+        match expression.split_last() {
+            Some((&"]", rest)) => expression = rest,
+            _ => {
+                io.write_string("[: missing ']'\n");
+                return 2;
+            }
+        }
+    }
+    match evaluate(expression) {
+        Some(true) => 0,
+        Some(false) => 1,
+        None => {
+            io.write_string("test: invalid expression\n");
+            2
+        }
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[
+        Command {
+            name: "source",
+            aliases: &["sh", "."],
+            usage: "source <file>",
+            summary: "Run a shell script (if/else/fi, while/done, exit)",
+            handler: cmd_source,
+        },
+        Command {
+            name: "test",
+            aliases: &["["],
+            usage: "test <expression>",
+            summary: "Check files, strings and numbers; sets $?",
+            handler: cmd_test,
+        },
+        Command {
+            name: "echo",
+            aliases: &[],
+            usage: "echo [-n] [-e] [text]...",
+            summary: "Print text; -e expands \\n, \\t, \\e and \\033 escapes",
+            handler: cmd_echo,
+        },
+        Command {
+            name: "true",
+            aliases: &[],
+            usage: "true",
+            summary: "Return success",
+            handler: cmd_true,
+        },
+        Command {
+            name: "false",
+            aliases: &[],
+            usage: "false",
+            summary: "Return failure",
+            handler: cmd_false,
+        },
+    ]);
+}
@@ -1,29 +1,29 @@
-use crate::vga_buffer;
+use crate::keyboard::{self, Key};
+use crate::{graphics, keymap, statusbar, vga_buffer};
+
+const FONT_PATH_LEN: usize = 64;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyboardLayout {
-    Qwerty,
-    Azerty,
-    Qwertz,
+    Us,
+    Uk,
+    German,
+    French,
+    Spanish,
     Dvorak,
+    /// A keymap file loaded with `loadkeys`.
+    Custom,
 }
 
 impl KeyboardLayout {
-    pub fn name(&self) -> &'static str {
-        match self {
-            KeyboardLayout::Qwerty => "QWERTY (US/UK)",
-            KeyboardLayout::Azerty => "AZERTY (French)",
-            KeyboardLayout::Qwertz => "QWERTZ (German)",
-            KeyboardLayout::Dvorak => "Dvorak",
-        }
-    }
-
     pub fn next(&self) -> KeyboardLayout {
         match self {
-            KeyboardLayout::Qwerty => KeyboardLayout::Azerty,
-            KeyboardLayout::Azerty => KeyboardLayout::Qwertz,
-            KeyboardLayout::Qwertz => KeyboardLayout::Dvorak,
-            KeyboardLayout::Dvorak => KeyboardLayout::Qwerty,
+            KeyboardLayout::Us => KeyboardLayout::Uk,
+            KeyboardLayout::Uk => KeyboardLayout::German,
+            KeyboardLayout::German => KeyboardLayout::French,
+            KeyboardLayout::French => KeyboardLayout::Spanish,
+            KeyboardLayout::Spanish => KeyboardLayout::Dvorak,
+            KeyboardLayout::Dvorak | KeyboardLayout::Custom => KeyboardLayout::Us,
         }
     }
 }
@@ -34,6 +34,34 @@ pub struct Settings {
     pub caps_lock_enabled: bool,
     pub syntax_highlighting: bool,
     pub editor_theme: EditorTheme,
+    pub save_history: bool,
+    pub status_bar: StatusBar,
+    console_font: [u8; FONT_PATH_LEN],
+    console_font_len: usize,
+}
+
+impl Settings {
+    /// Path of the PSF font loaded with `setfont` since boot, if any.
+    pub fn console_font(&self) -> Option<&str> {
+        if self.console_font_len == 0 {
+            return None;
+        }
+        core::str::from_utf8(&self.console_font[..self.console_font_len]).ok()
+    }
+
+    pub fn set_console_font(&mut self, path: &str) -> Result<(), &'static str> {
+        let path = path.as_bytes();
+        if path.len() > FONT_PATH_LEN {
+            return Err("font path too long");
+        }
+        self.console_font[..path.len()].copy_from_slice(path);
+        self.console_font_len = path.len();
+        Ok(())
+    }
+
+    pub fn clear_console_font(&mut self) {
+        self.console_font_len = 0;
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -61,22 +89,55 @@ impl EditorTheme {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusBar {
+    Off,
+    Top,
+    Bottom,
+}
+
+impl StatusBar {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusBar::Off => "OFF",
+            StatusBar::Top => "Top",
+            StatusBar::Bottom => "Bottom",
+        }
+    }
+
+    pub fn next(&self) -> StatusBar {
+        match self {
+            StatusBar::Off => StatusBar::Top,
+            StatusBar::Top => StatusBar::Bottom,
+            StatusBar::Bottom => StatusBar::Off,
+        }
+    }
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
-            keyboard_layout: KeyboardLayout::Qwerty,
+            keyboard_layout: KeyboardLayout::Us,
             caps_lock_enabled: false,
             syntax_highlighting: true,
             editor_theme: EditorTheme::Default,
+            save_history: false,
+            status_bar: StatusBar::Off,
+            console_font: [0; FONT_PATH_LEN],
+            console_font_len: 0,
         }
     }
 }
 
 static mut GLOBAL_SETTINGS: Settings = Settings {
-    keyboard_layout: KeyboardLayout::Qwerty,
+    keyboard_layout: KeyboardLayout::Us,
     caps_lock_enabled: false,
     syntax_highlighting: true,
     editor_theme: EditorTheme::Default,
+    save_history: false,
+    status_bar: StatusBar::Off,
+    console_font: [0; FONT_PATH_LEN],
+    console_font_len: 0,
 };
 
 pub fn get_settings() -> Settings {
@@ -87,216 +148,6 @@ pub fn set_settings(settings: Settings) {
     unsafe { GLOBAL_SETTINGS = settings; }
 }
 
-pub fn scancode_to_char(sc: u8, shift_pressed: bool) -> Option<char> {
-    let settings = get_settings();
-    let caps = settings.caps_lock_enabled ^ shift_pressed; 
-
-    match settings.keyboard_layout {
-        KeyboardLayout::Qwerty => qwerty_scancode_to_char(sc, caps, shift_pressed),
-        KeyboardLayout::Azerty => azerty_scancode_to_char(sc, caps, shift_pressed),
-        KeyboardLayout::Qwertz => qwertz_scancode_to_char(sc, caps, shift_pressed),
-        KeyboardLayout::Dvorak => dvorak_scancode_to_char(sc, caps, shift_pressed),
-    }
-}
-
-fn qwerty_scancode_to_char(sc: u8, caps: bool, shift: bool) -> Option<char> {
-    match sc {
-
-        0x02 => Some(if shift { '!' } else { '1' }),
-        0x03 => Some(if shift { '@' } else { '2' }),
-        0x04 => Some(if shift { '#' } else { '3' }),
-        0x05 => Some(if shift { '$' } else { '4' }),
-        0x06 => Some(if shift { '%' } else { '5' }),
-        0x07 => Some(if shift { '^' } else { '6' }),
-        0x08 => Some(if shift { '&' } else { '7' }),
-        0x09 => Some(if shift { '*' } else { '8' }),
-        0x0A => Some(if shift { '(' } else { '9' }),
-        0x0B => Some(if shift { ')' } else { '0' }),
-        0x0C => Some(if shift { '_' } else { '-' }),
-        0x0D => Some(if shift { '+' } else { '=' }),
-
-        0x10 => Some(if caps { 'Q' } else { 'q' }),
-        0x11 => Some(if caps { 'W' } else { 'w' }),
-        0x12 => Some(if caps { 'E' } else { 'e' }),
-        0x13 => Some(if caps { 'R' } else { 'r' }),
-        0x14 => Some(if caps { 'T' } else { 't' }),
-        0x15 => Some(if caps { 'Y' } else { 'y' }),
-        0x16 => Some(if caps { 'U' } else { 'u' }),
-        0x17 => Some(if caps { 'I' } else { 'i' }),
-        0x18 => Some(if caps { 'O' } else { 'o' }),
-        0x19 => Some(if caps { 'P' } else { 'p' }),
-        0x1A => Some(if shift { '{' } else { '[' }),
-        0x1B => Some(if shift { '}' } else { ']' }),
-
-        0x1E => Some(if caps { 'A' } else { 'a' }),
-        0x1F => Some(if caps { 'S' } else { 's' }),
-        0x20 => Some(if caps { 'D' } else { 'd' }),
-        0x21 => Some(if caps { 'F' } else { 'f' }),
-        0x22 => Some(if caps { 'G' } else { 'g' }),
-        0x23 => Some(if caps { 'H' } else { 'h' }),
-        0x24 => Some(if caps { 'J' } else { 'j' }),
-        0x25 => Some(if caps { 'K' } else { 'k' }),
-        0x26 => Some(if caps { 'L' } else { 'l' }),
-        0x27 => Some(if shift { ':' } else { ';' }),
-        0x28 => Some(if shift { '"' } else { '\'' }),
-        0x29 => Some(if shift { '~' } else { '`' }),
-
-        0x2C => Some(if caps { 'Z' } else { 'z' }),
-        0x2D => Some(if caps { 'X' } else { 'x' }),
-        0x2E => Some(if caps { 'C' } else { 'c' }),
-        0x2F => Some(if caps { 'V' } else { 'v' }),
-        0x30 => Some(if caps { 'B' } else { 'b' }),
-        0x31 => Some(if caps { 'N' } else { 'n' }),
-        0x32 => Some(if caps { 'M' } else { 'm' }),
-        0x33 => Some(if shift { '<' } else { ',' }),
-        0x34 => Some(if shift { '>' } else { '.' }),
-        0x35 => Some(if shift { '?' } else { '/' }),
-
-        0x39 => Some(' '), 
-        0x2B => Some(if shift { '|' } else { '\\' }),
-
-        _ => None,
-    }
-}
-
-fn azerty_scancode_to_char(sc: u8, caps: bool, shift: bool) -> Option<char> {
-    match sc {
-
-        0x02 => Some(if shift { '1' } else { '&' }),
-        0x03 => Some(if shift { '2' } else { 'é' }),
-        0x04 => Some(if shift { '3' } else { '"' }),
-        0x05 => Some(if shift { '4' } else { '\'' }),
-        0x06 => Some(if shift { '5' } else { '(' }),
-        0x07 => Some(if shift { '6' } else { '-' }),
-        0x08 => Some(if shift { '7' } else { 'è' }),
-        0x09 => Some(if shift { '8' } else { '_' }),
-        0x0A => Some(if shift { '9' } else { 'ç' }),
-        0x0B => Some(if shift { '0' } else { 'à' }),
-        0x0C => Some(if shift { '°' } else { ')' }),
-        0x0D => Some(if shift { '+' } else { '=' }),
-
-        0x10 => Some(if caps { 'A' } else { 'a' }),
-        0x11 => Some(if caps { 'Z' } else { 'z' }),
-        0x12 => Some(if caps { 'E' } else { 'e' }),
-        0x13 => Some(if caps { 'R' } else { 'r' }),
-        0x14 => Some(if caps { 'T' } else { 't' }),
-        0x15 => Some(if caps { 'Y' } else { 'y' }),
-        0x16 => Some(if caps { 'U' } else { 'u' }),
-        0x17 => Some(if caps { 'I' } else { 'i' }),
-        0x18 => Some(if caps { 'O' } else { 'o' }),
-        0x19 => Some(if caps { 'P' } else { 'p' }),
-
-        0x1E => Some(if caps { 'Q' } else { 'q' }),
-        0x1F => Some(if caps { 'S' } else { 's' }),
-        0x20 => Some(if caps { 'D' } else { 'd' }),
-        0x21 => Some(if caps { 'F' } else { 'f' }),
-        0x22 => Some(if caps { 'G' } else { 'g' }),
-        0x23 => Some(if caps { 'H' } else { 'h' }),
-        0x24 => Some(if caps { 'J' } else { 'j' }),
-        0x25 => Some(if caps { 'K' } else { 'k' }),
-        0x26 => Some(if caps { 'L' } else { 'l' }),
-        0x27 => Some(if caps { 'M' } else { 'm' }),
-
-        0x2C => Some(if caps { 'W' } else { 'w' }),
-        0x2D => Some(if caps { 'X' } else { 'x' }),
-        0x2E => Some(if caps { 'C' } else { 'c' }),
-        0x2F => Some(if caps { 'V' } else { 'v' }),
-        0x30 => Some(if caps { 'B' } else { 'b' }),
-        0x31 => Some(if caps { 'N' } else { 'n' }),
-        0x33 => Some(if shift { '?' } else { ',' }),
-        0x34 => Some(if shift { '.' } else { ';' }),
-        0x35 => Some(if shift { '/' } else { ':' }),
-
-        0x39 => Some(' '),
-        _ => None,
-    }
-}
-
-fn qwertz_scancode_to_char(sc: u8, caps: bool, shift: bool) -> Option<char> {
-    match sc {
-
-        0x02..=0x0B => qwerty_scancode_to_char(sc, caps, shift), 
-
-        0x10 => Some(if caps { 'Q' } else { 'q' }),
-        0x11 => Some(if caps { 'W' } else { 'w' }),
-        0x12 => Some(if caps { 'E' } else { 'e' }),
-        0x13 => Some(if caps { 'R' } else { 'r' }),
-        0x14 => Some(if caps { 'T' } else { 't' }),
-        0x15 => Some(if caps { 'Z' } else { 'z' }), 
-        0x16 => Some(if caps { 'U' } else { 'u' }),
-        0x17 => Some(if caps { 'I' } else { 'i' }),
-        0x18 => Some(if caps { 'O' } else { 'o' }),
-        0x19 => Some(if caps { 'P' } else { 'p' }),
-
-        0x1E => Some(if caps { 'A' } else { 'a' }),
-        0x1F => Some(if caps { 'S' } else { 's' }),
-        0x20 => Some(if caps { 'D' } else { 'd' }),
-        0x21 => Some(if caps { 'F' } else { 'f' }),
-        0x22 => Some(if caps { 'G' } else { 'g' }),
-        0x23 => Some(if caps { 'H' } else { 'h' }),
-        0x24 => Some(if caps { 'J' } else { 'j' }),
-        0x25 => Some(if caps { 'K' } else { 'k' }),
-        0x26 => Some(if caps { 'L' } else { 'l' }),
-
-        0x2C => Some(if caps { 'Y' } else { 'y' }), 
-        0x2D => Some(if caps { 'X' } else { 'x' }),
-        0x2E => Some(if caps { 'C' } else { 'c' }),
-        0x2F => Some(if caps { 'V' } else { 'v' }),
-        0x30 => Some(if caps { 'B' } else { 'b' }),
-        0x31 => Some(if caps { 'N' } else { 'n' }),
-        0x32 => Some(if caps { 'M' } else { 'm' }),
-        0x33 => Some(if shift { '<' } else { ',' }),
-        0x34 => Some(if shift { '>' } else { '.' }),
-        0x35 => Some(if shift { '?' } else { '/' }),
-
-        0x39 => Some(' '),
-        _ => qwerty_scancode_to_char(sc, caps, shift), 
-    }
-}
-
-fn dvorak_scancode_to_char(sc: u8, caps: bool, shift: bool) -> Option<char> {
-    match sc {
-
-        0x02..=0x0D => qwerty_scancode_to_char(sc, caps, shift),
-
-        0x10 => Some(if shift { '"' } else { '\'' }),
-        0x11 => Some(if shift { '<' } else { ',' }),
-        0x12 => Some(if shift { '>' } else { '.' }),
-        0x13 => Some(if caps { 'P' } else { 'p' }),
-        0x14 => Some(if caps { 'Y' } else { 'y' }),
-        0x15 => Some(if caps { 'F' } else { 'f' }),
-        0x16 => Some(if caps { 'G' } else { 'g' }),
-        0x17 => Some(if caps { 'C' } else { 'c' }),
-        0x18 => Some(if caps { 'R' } else { 'r' }),
-        0x19 => Some(if caps { 'L' } else { 'l' }),
-
-        0x1E => Some(if caps { 'A' } else { 'a' }),
-        0x1F => Some(if caps { 'O' } else { 'o' }),
-        0x20 => Some(if caps { 'E' } else { 'e' }),
-        0x21 => Some(if caps { 'U' } else { 'u' }),
-        0x22 => Some(if caps { 'I' } else { 'i' }),
-        0x23 => Some(if caps { 'D' } else { 'd' }),
-        0x24 => Some(if caps { 'H' } else { 'h' }),
-        0x25 => Some(if caps { 'T' } else { 't' }),
-        0x26 => Some(if caps { 'N' } else { 'n' }),
-        0x27 => Some(if caps { 'S' } else { 's' }),
-
-        0x2C => Some(if shift { ':' } else { ';' }),
-        0x2D => Some(if caps { 'Q' } else { 'q' }),
-        0x2E => Some(if caps { 'J' } else { 'j' }),
-        0x2F => Some(if caps { 'K' } else { 'k' }),
-        0x30 => Some(if caps { 'X' } else { 'x' }),
-        0x31 => Some(if caps { 'B' } else { 'b' }),
-        0x32 => Some(if caps { 'M' } else { 'm' }),
-        0x33 => Some(if caps { 'W' } else { 'w' }),
-        0x34 => Some(if caps { 'V' } else { 'v' }),
-        0x35 => Some(if caps { 'Z' } else { 'z' }),
-
-        0x39 => Some(' '),
-        _ => None,
-    }
-}
-
 pub fn get_caps_lock_state() -> bool {
     get_settings().caps_lock_enabled
 }
@@ -310,7 +161,7 @@ pub fn toggle_caps_lock() {
 pub fn show_settings_menu(writer: &mut vga_buffer::Writer) {
     let mut settings = get_settings();
     let mut selected = 0;
-    let menu_items = 3; 
+    let menu_items = 7; 
 
     loop {
         writer.clear_screen();
@@ -322,7 +173,7 @@ pub fn show_settings_menu(writer: &mut vga_buffer::Writer) {
             writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Black, vga_buffer::Color::White);
         }
         writer.write_string("1. Keyboard Layout: ");
-        writer.write_string(settings.keyboard_layout.name());
+        writer.write_string(keymap::name());
         writer.write_string("\n");
         writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
 
@@ -348,62 +199,89 @@ pub fn show_settings_menu(writer: &mut vga_buffer::Writer) {
         writer.write_string("\n");
         writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
 
+        if selected == 3 {
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Black, vga_buffer::Color::White);
+        }
+        writer.write_string("4. Save History to ~/.history: ");
+        writer.write_string(if settings.save_history { "ON" } else { "OFF" });
+        writer.write_string("\n");
+        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+
+        if selected == 4 {
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Black, vga_buffer::Color::White);
+        }
+        writer.write_string("5. Screen Mode: ");
+        writer.write_string(graphics::text_mode().name());
+        writer.write_string("\n");
+        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+
+        if selected == 5 {
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Black, vga_buffer::Color::White);
+        }
+        writer.write_string("6. Console Font: ");
+        writer.write_string(settings.console_font().unwrap_or("Default"));
+        writer.write_string("\n");
+        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+
+        if selected == 6 {
+            writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Black, vga_buffer::Color::White);
+        }
+        writer.write_string("7. Status Bar: ");
+        writer.write_string(settings.status_bar.name());
+        writer.write_string("\n");
+        writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+
         writer.write_string("\nUse Arrow Keys to navigate, Enter to change, ESC to exit\n");
         writer.write_string("Current layout test: ");
 
         writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
         writer.write_string("Try Shift+8 = ");
-        if let Some(c) = scancode_to_char(0x09, true) { 
-            writer.write_byte(c as u8);
+        if let Some(c) = keymap::scancode_to_char(0x09, true) { 
+            writer.write_char(c);
         }
         writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
         writer.write_string(", normal 8 = ");
-        if let Some(c) = scancode_to_char(0x09, false) {
-            writer.write_byte(c as u8);
+        if let Some(c) = keymap::scancode_to_char(0x09, false) {
+            writer.write_char(c);
         }
         writer.write_string("\nPress Caps Lock key to toggle caps state\n");
 
-        let mut shift_pressed = false;
-        let key = crate::read_scancode();
-
-        match key {
-            0x2A | 0x36 => { shift_pressed = true; }
-            0xAA | 0xB6 => { shift_pressed = false; }
-            _ => {}
-        }
-
-        if key >= 0x80 { continue; }
-
-        match key {
-            0x01 => break, 
-            0x1C => { 
+        match keyboard::read_key().key {
+            Key::Escape => break,
+            Key::Enter => {
+                // Caps Lock may have been toggled since the copy was taken.
+                settings.caps_lock_enabled = get_caps_lock_state();
                 match selected {
                     0 => settings.keyboard_layout = settings.keyboard_layout.next(),
                     1 => settings.syntax_highlighting = !settings.syntax_highlighting,
                     2 => settings.editor_theme = settings.editor_theme.next(),
+                    3 => settings.save_history = !settings.save_history,
+                    4 => { graphics::set_mode(graphics::text_mode().next_text()); }
+                    5 => {
+                        graphics::reset_font();
+                        settings.clear_console_font();
+                    }
+                    6 => settings.status_bar = settings.status_bar.next(),
                     _ => {}
                 }
                 set_settings(settings);
+                if selected == 6 {
+                    statusbar::apply();
+                }
             }
-            0x48 => { 
+            Key::Up => {
                 selected = if selected == 0 { menu_items - 1 } else { selected - 1 };
             }
-            0x50 => { 
+            Key::Down => {
                 selected = (selected + 1) % menu_items;
             }
-            0x3A => { 
-                toggle_caps_lock();
-
+            Key::CapsLock => {
                 writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Yellow, vga_buffer::Color::Black);
                 writer.write_string(" CAPS TOGGLED! ");
                 writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
             }
-            _ => {
-
-                if let Some(c) = scancode_to_char(key, shift_pressed) {
-                    writer.write_byte(c as u8);
-                }
-            }
+            Key::Char(c) => writer.write_char(c),
+            _ => {}
         }
     }
 }
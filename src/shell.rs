@@ -0,0 +1,288 @@
+use crate::args;
+use crate::commands;
+use crate::console::{self, CONSOLE_COUNT};
+use crate::env;
+use crate::file_system;
+use crate::io::{Io, PipeBuffer, Sink};
+use crate::statusbar;
+use crate::vga_buffer;
+
+const MAX_STAGES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Redirect {
+    Truncate,
+    Append,
+}
+
+#[derive(Clone, Copy)]
+struct Stage<'a> {
+    start: usize,
+    end: usize,
+    input: Option<&'a str>,
+    output: Option<(&'a str, Redirect)>,
+}
+
+fn print_error(writer: &mut vga_buffer::Writer, message: &str, detail: &str) {
+    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::Red, vga_buffer::Color::Black);
+    writer.write_string(message);
+    writer.write_string(detail);
+    writer.write_string("\n");
+    writer.color_code = vga_buffer::ColorCode::new(vga_buffer::Color::White, vga_buffer::Color::Black);
+}
+
+fn parse_stages<'a>(
+    argv: &mut [&'a str],
+    operators: &[bool],
+    stages: &mut [Stage<'a>; MAX_STAGES],
+) -> Result<usize, &'static str> {
+    let mut count = 0;
+    let mut words = 0;
+    let mut stage = Stage { start: 0, end: 0, input: None, output: None };
+    let mut i = 0;
+
+    while i <= argv.len() {
+        if i == argv.len() || (operators[i] && argv[i] == "|") {
+            if words == 0 {
+                return Err("Syntax error: missing command");
+            }
+            if count >= MAX_STAGES {
+                return Err("Too many pipeline stages");
+            }
+            stage.end = stage.start + words;
+            stages[count] = stage;
+            count += 1;
+            stage = Stage { start: i + 1, end: 0, input: None, output: None };
+            words = 0;
+            i += 1;
+            continue;
+        }
+
+        if operators[i] {
+            let target = match argv.get(i + 1) {
+                Some(&target) if !operators[i + 1] => target,
+                _ => return Err("Syntax error: missing file name after redirection"),
+            };
+            match argv[i] {
+                "<" => stage.input = Some(target),
+                ">" => stage.output = Some((target, Redirect::Truncate)),
+                _ => stage.output = Some((target, Redirect::Append)),
+            }
+            i += 2;
+            continue;
+        }
+
+        argv[stage.start + words] = argv[i];
+        words += 1;
+        i += 1;
+    }
+    Ok(count)
+}
+
+fn write_output(name: &str, data: &[u8], mode: Redirect) -> Result<(), &'static str> {
+    let mut content = [0u8; file_system::MAX_FILE_SIZE];
+    let mut len = 0;
+
+    if mode == Redirect::Append {
+        file_system::with_fs(|fs| {
+            if let Ok(existing) = fs.read_file(name) {
+                content[..existing.len()].copy_from_slice(existing);
+                len = existing.len();
+            }
+        });
+    }
+    if len + data.len() > content.len() {
+        return Err("Output too large for file: ");
+    }
+    content[len..len + data.len()].copy_from_slice(data);
+    len += data.len();
+
+    file_system::with_fs_mut(|fs| fs.write_file(name, &content[..len]))
+        .map_err(|_| "Could not write file: ")
+}
+
+static mut LAST_STATUS: [i32; CONSOLE_COUNT] = [0; CONSOLE_COUNT];
+
+pub fn last_status() -> i32 {
+    unsafe { LAST_STATUS[console::active()] }
+}
+
+fn set_last_status(status: i32) -> i32 {
+    unsafe { LAST_STATUS[console::active()] = status };
+    status
+}
+
+pub fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = b' ';
+    for (i, b) in line.bytes().enumerate() {
+        match (quote, b) {
+            (None, b'#') if previous == b' ' || previous == b'\t' => return &line[..i],
+            (None, b'\'') | (None, b'"') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            _ => {}
+        }
+        previous = b;
+    }
+    line
+}
+
+fn append(out: &mut [u8], len: &mut usize, data: &[u8]) -> Result<(), &'static str> {
+    if *len + data.len() > out.len() {
+        return Err("Command line too long after expansion");
+    }
+    out[*len..*len + data.len()].copy_from_slice(data);
+    *len += data.len();
+    Ok(())
+}
+
+fn expand_aliases<'a>(line: &str, out: &'a mut [u8]) -> Result<&'a str, &'static str> {
+    let bytes = line.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+    let mut quote = None;
+    let mut command_position = true;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if quote.is_none() && command_position && b != b' ' && b != b'\t' {
+            command_position = false;
+            let end = bytes[i..]
+                .iter()
+                .position(|&c| c == b' ' || c == b'\t' || args::is_operator_byte(c))
+                .map_or(bytes.len(), |p| i + p);
+            if let Some(command) = env::alias(&line[i..end]) {
+                append(out, &mut len, command.as_bytes())?;
+                i = end;
+                continue;
+            }
+        }
+        match (quote, b) {
+            (None, b'\'') | (None, b'"') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b'|') => command_position = true,
+            (_, b'\\') if i + 1 < bytes.len() && quote != Some(b'\'') => {
+                append(out, &mut len, &bytes[i..i + 2])?;
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+        append(out, &mut len, &bytes[i..i + 1])?;
+        i += 1;
+    }
+
+    let out: &'a [u8] = out;
+    core::str::from_utf8(&out[..len]).map_err(|_| "Invalid characters in command line")
+}
+
+fn write_variable(name: &str, out: &mut dyn Sink) {
+    match name {
+        "?" => write!(out, "{}", last_status()),
+        _ => out.write_string(env::get(name).unwrap_or("")),
+    }
+}
+
+pub fn execute(io: &mut Io, line: &str) -> i32 {
+    let status = run_line(io, line);
+    // The command may have changed the directory, layout or settings the status bar shows.
+    statusbar::draw();
+    set_last_status(status)
+}
+
+fn run_line(io: &mut Io, line: &str) -> i32 {
+    let mut aliased = [0u8; 512];
+    let line = match expand_aliases(strip_comment(line), &mut aliased) {
+        Ok(line) => line,
+        Err(e) => {
+            print_error(io.console, e, "");
+            return 2;
+        }
+    };
+
+    let mut storage = [0u8; 512];
+    let mut argv = [""; args::MAX_ARGS];
+    let mut operators = [false; args::MAX_ARGS];
+    let argc = match args::tokenize(line, &mut storage, &mut argv, &mut operators, write_variable) {
+        Ok(argc) => argc,
+        Err(e) => {
+            print_error(io.console, e, "");
+            return 2;
+        }
+    };
+    if argc == 0 {
+        return last_status();
+    }
+
+    if argc == 1 && !operators[0] {
+        if let Some((name, value)) = env::parse_assignment(argv[0]) {
+            return match env::set(name, value) {
+                Ok(_) => 0,
+                Err(e) => {
+                    print_error(io.console, e, "");
+                    1
+                }
+            };
+        }
+    }
+
+    let mut stages = [Stage { start: 0, end: 0, input: None, output: None }; MAX_STAGES];
+    let stage_count = match parse_stages(&mut argv[..argc], &operators[..argc], &mut stages) {
+        Ok(count) => count,
+        Err(e) => {
+            print_error(io.console, e, "");
+            return 2;
+        }
+    };
+
+    let mut input = PipeBuffer::new();
+    let mut output = PipeBuffer::new();
+    let mut has_input = false;
+    let mut status = 0;
+
+    for (index, stage) in stages[..stage_count].iter().enumerate() {
+        let stage_argv = &argv[stage.start..stage.end];
+        let command = match commands::find(stage_argv[0]) {
+            Some(command) => command,
+            None => {
+                print_error(io.console, "Unknown command: ", stage_argv[0]);
+                return 127;
+            }
+        };
+
+        if let Some(name) = stage.input {
+            let found = file_system::with_fs(|fs| fs.read_file(name).map(|data| input.fill(data)).is_ok());
+            if !found {
+                print_error(io.console, "No such file: ", name);
+                return 1;
+            }
+            has_input = true;
+        }
+
+        let last = index + 1 == stage_count;
+        let capture = !last || stage.output.is_some();
+        output.clear();
+        {
+            let (console, parent) = io.parts();
+            let stdin = if has_input { Some(input.as_bytes()) } else { None };
+            let sink: Option<&mut dyn Sink> = if capture { Some(&mut output) } else { parent };
+            let mut stage_io = Io::new(console, stdin, sink);
+            status = (command.handler)(&mut stage_io, stage_argv);
+        }
+
+        if output.overflowed() {
+            print_error(io.console, "Warning: output truncated in ", stage_argv[0]);
+        }
+        if let Some((name, mode)) = stage.output {
+            if let Err(e) = write_output(name, output.as_bytes(), mode) {
+                print_error(io.console, e, name);
+                return 1;
+            }
+            output.clear();
+        }
+
+        core::mem::swap(&mut input, &mut output);
+        has_input = true;
+    }
+    status
+}
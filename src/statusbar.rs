@@ -0,0 +1,166 @@
+use crate::io::Sink;
+use crate::settings::{self, StatusBar};
+use crate::vga_buffer::{self, Color, ColorCode, ScreenChar, MAX_WIDTH};
+use crate::{console, file_system, graphics, interrupts, keymap, memory, rtc};
+
+const BAR_COLOR: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+const CAPS_COLOR: ColorCode = ColorCode::new(Color::White, Color::Red);
+// Twice a second, so the seconds never visibly skip.
+const REFRESH_TICKS: u64 = interrupts::TICKS_PER_SECOND as u64 / 2;
+
+/// One row of the bar, filled through `write!`.
+#[derive(Clone, Copy)]
+struct Line {
+    text: [u8; MAX_WIDTH],
+    colors: [ColorCode; MAX_WIDTH],
+    len: usize,
+    color: ColorCode,
+}
+
+impl Line {
+    const fn new() -> Self {
+        Line { text: [b' '; MAX_WIDTH], colors: [BAR_COLOR; MAX_WIDTH], len: 0, color: BAR_COLOR }
+    }
+}
+
+impl Sink for Line {
+    fn write_byte(&mut self, byte: u8) {
+        if self.len < MAX_WIDTH {
+            self.text[self.len] = byte;
+            self.colors[self.len] = self.color;
+            self.len += 1;
+        }
+    }
+
+    fn set_color(&mut self, color: ColorCode) {
+        self.color = color;
+    }
+
+    fn column(&self) -> usize {
+        self.len
+    }
+}
+
+fn write_size(line: &mut Line, bytes: u64) {
+    if bytes >= 1 << 30 {
+        write!(line, "{}G", bytes >> 30);
+    } else if bytes >= 1 << 20 {
+        write!(line, "{}M", bytes >> 20);
+    } else {
+        write!(line, "{}K", bytes >> 10);
+    }
+}
+
+/// Reserves the row chosen in the settings and fits the consoles around it.
+pub fn apply() {
+    interrupts::without_interrupts(|| {
+        match settings::get_settings().status_bar {
+            StatusBar::Off => vga_buffer::set_reserved_rows(0, 0),
+            StatusBar::Top => vga_buffer::set_reserved_rows(1, 0),
+            StatusBar::Bottom => vga_buffer::set_reserved_rows(0, 1),
+        }
+        console::resize();
+    });
+}
+
+/// What the bar shows besides the clock. The main loop builds it in `draw`, so the timer
+/// interrupt never reads the file system, the keymap or the settings while they change.
+#[derive(Clone, Copy)]
+struct Snapshot {
+    row: Option<usize>,
+    path: Line,
+    right: Line,
+}
+
+static mut SNAPSHOT: Snapshot = Snapshot { row: None, path: Line::new(), right: Line::new() };
+
+/// Called from the timer interrupt; only reads the snapshot, the clock and the screen size,
+/// and everything that changes the screen size does so with interrupts off.
+pub fn tick() {
+    if interrupts::ticks().is_multiple_of(REFRESH_TICKS) {
+        render();
+    }
+}
+
+/// Takes a new snapshot of the directory, Caps Lock, layout and memory use and redraws.
+pub fn draw() {
+    let snapshot = snapshot();
+    interrupts::without_interrupts(|| {
+        unsafe { SNAPSHOT = snapshot };
+        render();
+    });
+}
+
+fn snapshot() -> Snapshot {
+    let row = match settings::get_settings().status_bar {
+        StatusBar::Off => None,
+        StatusBar::Top => Some(0),
+        StatusBar::Bottom => Some(vga_buffer::screen_height() - 1),
+    };
+
+    let mut path = Line::new();
+    file_system::with_fs(|fs| {
+        let mut empty = true;
+        for name in fs.current_path() {
+            path.write_byte(b'/');
+            for &byte in name {
+                path.write_byte(byte);
+            }
+            empty = false;
+        }
+        if empty {
+            path.write_byte(b'/');
+        }
+    });
+
+    let mut right = Line::new();
+    if settings::get_caps_lock_state() {
+        right.set_color(CAPS_COLOR);
+        right.write_string(" CAPS ");
+        right.set_color(BAR_COLOR);
+        right.write_string("  ");
+    }
+    right.write_string(keymap::name());
+    let (used, total) = memory::usage();
+    right.write_string("  mem ");
+    write_size(&mut right, used);
+    right.write_byte(b'/');
+    write_size(&mut right, total);
+    right.write_byte(b' ');
+
+    Snapshot { row, path, right }
+}
+
+fn render() {
+    let snapshot = unsafe { &*(&raw const SNAPSHOT) };
+    let Some(row) = snapshot.row else {
+        return;
+    };
+    if !graphics::current_mode().is_text() {
+        return;
+    }
+
+    let mut left = Line::new();
+    let now = rtc::now();
+    write!(left, " {:02}:{:02}:{:02}  ", now.hour, now.minute, now.second);
+    for col in 0..snapshot.path.len {
+        left.set_color(snapshot.path.colors[col]);
+        left.write_byte(snapshot.path.text[col]);
+    }
+    let right = &snapshot.right;
+
+    // The right part wins when the path is too long to fit next to it.
+    let width = vga_buffer::width();
+    let right_start = width.saturating_sub(right.len);
+    let screen = unsafe { &mut *vga_buffer::hardware_buffer() };
+    for col in 0..width {
+        let (byte, color) = if col >= right_start {
+            (right.text[col - right_start], right.colors[col - right_start])
+        } else if col < left.len {
+            (left.text[col], left.colors[col])
+        } else {
+            (b' ', BAR_COLOR)
+        };
+        screen.chars[row][col].write(ScreenChar { ascii_character: byte, color_code: color });
+    }
+}
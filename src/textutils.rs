@@ -0,0 +1,517 @@
+use crate::args;
+use crate::commands::{self, Command};
+use crate::file_system;
+use crate::io::{Io, Sink, PIPE_SIZE};
+
+const MAX_LINES: usize = 256;
+const SORT_SIZE: usize = if file_system::MAX_FILE_SIZE > PIPE_SIZE { file_system::MAX_FILE_SIZE } else { PIPE_SIZE };
+
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    let empty = data.is_empty();
+    data.split(|&b| b == b'\n').filter(move |_| !empty)
+}
+
+fn write_line(io: &mut Io, line: &[u8]) {
+    for &byte in line {
+        io.write_byte(byte);
+    }
+    io.write_byte(b'\n');
+}
+
+fn write_number(io: &mut Io, n: usize, width: usize) {
+    write!(io, "{:>width$}", n);
+}
+
+fn write_hex(io: &mut Io, value: usize, digits: usize) {
+    write!(io, "{:0digits$x}", value);
+}
+
+fn report(io: &mut Io, command: &str, message: &str, detail: &str) {
+    io.write_string(command);
+    io.write_string(": ");
+    io.write_string(message);
+    io.write_string(detail);
+    io.write_string("\n");
+}
+
+fn for_each_input<F>(io: &mut Io, command: &str, files: &[&str], mut f: F) -> i32
+where
+    F: FnMut(&mut Io, Option<&str>, &[u8]),
+{
+    if files.is_empty() {
+        return match io.stdin.as_mut().map(|stdin| stdin.read_all()) {
+            Some(data) => {
+                f(io, None, data);
+                0
+            }
+            None => {
+                report(io, command, "no input files and nothing piped in", "");
+                2
+            }
+        };
+    }
+
+    let mut status = 0;
+    let mut buffer = [0u8; file_system::MAX_FILE_SIZE];
+    for &name in files {
+        let len = file_system::with_fs(|fs| {
+            fs.read_file(name).ok().map(|data| {
+                buffer[..data.len()].copy_from_slice(data);
+                data.len()
+            })
+        });
+        match len {
+            Some(len) => f(io, Some(name), &buffer[..len]),
+            None => {
+                report(io, command, "No such file: ", name);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn parse_line_count<'a>(argv: &[&'a str], files: &mut [&'a str; args::MAX_ARGS]) -> Option<(usize, usize)> {
+    let mut count = 10;
+    let mut file_count = 0;
+    let mut i = 1;
+    while i < argv.len() {
+        let arg = argv[i];
+        if arg == "-n" {
+            count = args::parse_number(argv.get(i + 1)?)?;
+            i += 1;
+        } else if let Some(value) = arg.strip_prefix("--lines=") {
+            count = args::parse_number(value)?;
+        } else if let Some(value) = arg.strip_prefix("-n") {
+            count = args::parse_number(value)?;
+        } else if let Some(value) = arg.strip_prefix('-').filter(|v| !v.is_empty()) {
+            count = args::parse_number(value)?;
+        } else {
+            files[file_count] = arg;
+            file_count += 1;
+        }
+        i += 1;
+    }
+    Some((count, file_count))
+}
+
+fn write_header(io: &mut Io, name: Option<&str>, multiple: bool, first: &mut bool) {
+    if let (true, Some(name)) = (multiple, name) {
+        if !*first {
+            io.write_byte(b'\n');
+        }
+        io.write_string("==> ");
+        io.write_string(name);
+        io.write_string(" <==\n");
+    }
+    *first = false;
+}
+
+fn cmd_head(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut files = [""; args::MAX_ARGS];
+    let (count, file_count) = match parse_line_count(argv, &mut files) {
+        Some(parsed) => parsed,
+        None => {
+            io.write_string("Usage: head [-n N] [file]...\n");
+            return 2;
+        }
+    };
+    let multiple = file_count > 1;
+    let mut first = true;
+    for_each_input(io, "head", &files[..file_count], |io, name, data| {
+        write_header(io, name, multiple, &mut first);
+        for line in lines(data).take(count) {
+            write_line(io, line);
+        }
+    })
+}
+
+fn cmd_tail(io: &mut Io, argv: &[&str]) -> i32 {
+    let mut files = [""; args::MAX_ARGS];
+    let (count, file_count) = match parse_line_count(argv, &mut files) {
+        Some(parsed) => parsed,
+        None => {
+            io.write_string("Usage: tail [-n N] [file]...\n");
+            return 2;
+        }
+    };
+    let multiple = file_count > 1;
+    let mut first = true;
+    for_each_input(io, "tail", &files[..file_count], |io, name, data| {
+        write_header(io, name, multiple, &mut first);
+        let total = lines(data).count();
+        for line in lines(data).skip(total.saturating_sub(count)) {
+            write_line(io, line);
+        }
+    })
+}
+
+fn cmd_wc(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("lwc", &[]).is_err() {
+        io.write_string("Usage: wc [-l] [-w] [-c] [file]...\n");
+        return 2;
+    }
+    let all = !flags.has('l') && !flags.has('w') && !flags.has('c');
+    let show = [all || flags.has('l'), all || flags.has('w'), all || flags.has('c')];
+    let mut totals = [0usize; 3];
+    let mut inputs = 0;
+
+    let status = for_each_input(io, "wc", flags.positional(), |io, name, data| {
+        let line_count = data.iter().filter(|&&b| b == b'\n').count();
+        let word_count = data
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .count();
+        let counts = [line_count, word_count, data.len()];
+        for i in 0..3 {
+            totals[i] += counts[i];
+            if show[i] {
+                write_number(io, counts[i], 7);
+            }
+        }
+        if let Some(name) = name {
+            io.write_byte(b' ');
+            io.write_string(name);
+        }
+        io.write_byte(b'\n');
+        inputs += 1;
+    });
+
+    if inputs > 1 {
+        for i in 0..3 {
+            if show[i] {
+                write_number(io, totals[i], 7);
+            }
+        }
+        io.write_string(" total\n");
+    }
+    status
+}
+
+fn leading_number(line: &[u8]) -> i64 {
+    let mut i = 0;
+    while i < line.len() && line[i] == b' ' {
+        i += 1;
+    }
+    let negative = line.get(i) == Some(&b'-');
+    if negative {
+        i += 1;
+    }
+    let mut value: i64 = 0;
+    while i < line.len() && line[i].is_ascii_digit() {
+        value = value.saturating_mul(10).saturating_add((line[i] - b'0') as i64);
+        i += 1;
+    }
+    if negative { -value } else { value }
+}
+
+fn cmd_sort(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("rnu", &[]).is_err() {
+        io.write_string("Usage: sort [-r] [-n] [-u] [file]...\n");
+        return 2;
+    }
+    let (reverse, numeric, unique) = (flags.has('r'), flags.has('n'), flags.has('u'));
+
+    // Sorting needs all the input at once; a whole file or pipe always fits.
+    let mut data = [0u8; SORT_SIZE];
+    let mut len = 0;
+    let mut overflowed = false;
+    let status = for_each_input(io, "sort", flags.positional(), |_, _, input| {
+        let newline = !input.is_empty() && !input.ends_with(b"\n");
+        if len + input.len() + newline as usize > data.len() {
+            overflowed = true;
+            return;
+        }
+        data[len..len + input.len()].copy_from_slice(input);
+        len += input.len();
+        if newline {
+            data[len] = b'\n';
+            len += 1;
+        }
+    });
+    if overflowed {
+        report(io, "sort", "input too large", "");
+        return 1;
+    }
+
+    let mut sorted: [&[u8]; MAX_LINES] = [&[]; MAX_LINES];
+    let mut count = 0;
+    for line in lines(&data[..len]) {
+        if count >= MAX_LINES {
+            report(io, "sort", "too many lines", "");
+            return 1;
+        }
+        sorted[count] = line;
+        count += 1;
+    }
+    sorted[..count].sort_unstable_by(|a, b| {
+        let order = if numeric {
+            leading_number(a).cmp(&leading_number(b)).then_with(|| a.cmp(b))
+        } else {
+            a.cmp(b)
+        };
+        if reverse { order.reverse() } else { order }
+    });
+
+    for i in 0..count {
+        if unique && i > 0 && sorted[i] == sorted[i - 1] {
+            continue;
+        }
+        write_line(io, sorted[i]);
+    }
+    status
+}
+
+fn cmd_uniq(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("cd", &[]).is_err() {
+        io.write_string("Usage: uniq [-c] [-d] [file]...\n");
+        return 2;
+    }
+    let (counts, duplicates_only) = (flags.has('c'), flags.has('d'));
+
+    for_each_input(io, "uniq", flags.positional(), |io, _, data| {
+        let mut previous: Option<&[u8]> = None;
+        let mut repeats = 0;
+        let emit = |io: &mut Io, line: &[u8], repeats: usize| {
+            if duplicates_only && repeats < 2 {
+                return;
+            }
+            if counts {
+                write_number(io, repeats, 7);
+                io.write_byte(b' ');
+            }
+            write_line(io, line);
+        };
+        for line in lines(data) {
+            match previous {
+                Some(prev) if prev == line => repeats += 1,
+                Some(prev) => {
+                    emit(io, prev, repeats);
+                    previous = Some(line);
+                    repeats = 1;
+                }
+                None => {
+                    previous = Some(line);
+                    repeats = 1;
+                }
+            }
+        }
+        if let Some(prev) = previous {
+            emit(io, prev, repeats);
+        }
+    })
+}
+
+fn eq_byte(pattern: u8, c: u8, ignore_case: bool) -> bool {
+    pattern == b'.' || pattern == c || (ignore_case && pattern.eq_ignore_ascii_case(&c))
+}
+
+fn atom(pattern: &[u8]) -> (u8, bool, usize) {
+    match pattern {
+        [b'\\', c, ..] => (*c, true, 2),
+        [c, ..] => (*c, false, 1),
+        [] => (0, false, 0),
+    }
+}
+
+fn atom_matches(c: u8, literal: bool, text: u8, ignore_case: bool) -> bool {
+    if literal { c == text || (ignore_case && c.eq_ignore_ascii_case(&text)) } else { eq_byte(c, text, ignore_case) }
+}
+
+fn match_here(pattern: &[u8], text: &[u8], ignore_case: bool) -> bool {
+    if pattern.is_empty() {
+        return true;
+    }
+    if pattern == b"$" {
+        return text.is_empty();
+    }
+    let (c, literal, len) = atom(pattern);
+    match pattern.get(len) {
+        Some(b'*') => match_repeat(c, literal, &pattern[len + 1..], text, 0, ignore_case),
+        Some(b'+') => match_repeat(c, literal, &pattern[len + 1..], text, 1, ignore_case),
+        Some(b'?') => {
+            (!text.is_empty()
+                && atom_matches(c, literal, text[0], ignore_case)
+                && match_here(&pattern[len + 1..], &text[1..], ignore_case))
+                || match_here(&pattern[len + 1..], text, ignore_case)
+        }
+        _ => {
+            !text.is_empty()
+                && atom_matches(c, literal, text[0], ignore_case)
+                && match_here(&pattern[len..], &text[1..], ignore_case)
+        }
+    }
+}
+
+fn match_repeat(c: u8, literal: bool, rest: &[u8], text: &[u8], min: usize, ignore_case: bool) -> bool {
+    let mut count = 0;
+    while count < text.len() && atom_matches(c, literal, text[count], ignore_case) {
+        count += 1;
+    }
+    loop {
+        if count >= min && match_here(rest, &text[count..], ignore_case) {
+            return true;
+        }
+        if count == 0 || count <= min {
+            return false;
+        }
+        count -= 1;
+    }
+}
+
+pub fn regex_match(pattern: &str, text: &[u8], ignore_case: bool) -> bool {
+    let pattern = pattern.as_bytes();
+    if let Some(anchored) = pattern.strip_prefix(b"^") {
+        return match_here(anchored, text, ignore_case);
+    }
+    (0..=text.len()).any(|start| match_here(pattern, &text[start..], ignore_case))
+}
+
+fn cmd_grep(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    let (pattern, files) = match flags.positional().split_first() {
+        Some((&pattern, files)) if flags.check("ivnc", &[]).is_ok() => (pattern, files),
+        _ => {
+            io.write_string("Usage: grep [-i] [-v] [-n] [-c] <pattern> [file]...\n");
+            return 2;
+        }
+    };
+    let (ignore_case, invert, numbers, count_only) = (flags.has('i'), flags.has('v'), flags.has('n'), flags.has('c'));
+    let multiple = files.len() > 1;
+    let mut matched = 0;
+
+    let status = for_each_input(io, "grep", files, |io, name, data| {
+        let mut count = 0;
+        for (index, line) in lines(data).enumerate() {
+            if regex_match(pattern, line, ignore_case) == invert {
+                continue;
+            }
+            count += 1;
+            if count_only {
+                continue;
+            }
+            if let (true, Some(name)) = (multiple, name) {
+                io.write_string(name);
+                io.write_byte(b':');
+            }
+            if numbers {
+                write_number(io, index + 1, 0);
+                io.write_byte(b':');
+            }
+            write_line(io, line);
+        }
+        if count_only {
+            if let (true, Some(name)) = (multiple, name) {
+                io.write_string(name);
+                io.write_byte(b':');
+            }
+            write_number(io, count, 0);
+            io.write_byte(b'\n');
+        }
+        matched += count;
+    });
+
+    match (status, matched) {
+        (0, 0) => 1,
+        (0, _) => 0,
+        (status, _) => status,
+    }
+}
+
+fn cmd_hexdump(io: &mut Io, argv: &[&str]) -> i32 {
+    let flags = args::Flags::parse(&argv[1..]);
+    if flags.check("C", &[]).is_err() {
+        io.write_string("Usage: hexdump [-C] [file]...\n");
+        return 2;
+    }
+    let canonical = flags.has('C');
+    let mut offset = 0;
+
+    let status = for_each_input(io, "hexdump", flags.positional(), |io, _, data| {
+        for chunk in data.chunks(16) {
+            write_hex(io, offset, if canonical { 8 } else { 7 });
+            for (i, &byte) in chunk.iter().enumerate() {
+                io.write_byte(b' ');
+                if canonical && i == 8 {
+                    io.write_byte(b' ');
+                }
+                write_hex(io, byte as usize, 2);
+            }
+            if canonical {
+                for i in chunk.len()..16 {
+                    io.write_string(if i == 8 { "    " } else { "   " });
+                }
+                io.write_string("  |");
+                for &byte in chunk {
+                    io.write_byte(if (0x20..0x7F).contains(&byte) { byte } else { b'.' });
+                }
+                io.write_byte(b'|');
+            }
+            io.write_byte(b'\n');
+            offset += chunk.len();
+        }
+    });
+    if status == 0 || offset > 0 {
+        write_hex(io, offset, if canonical { 8 } else { 7 });
+        io.write_byte(b'\n');
+    }
+    status
+}
+
+pub fn register_commands() {
+    commands::register_all(&[
+        Command {
+            name: "grep",
+            aliases: &[],
+            usage: "grep [-ivnc] <regex> [file]...",
+            summary: "Print lines matching a pattern (. * + ? ^ $)",
+            handler: cmd_grep,
+        },
+        Command {
+            name: "head",
+            aliases: &[],
+            usage: "head [-n N] [file]...",
+            summary: "Print the first lines of input",
+            handler: cmd_head,
+        },
+        Command {
+            name: "tail",
+            aliases: &[],
+            usage: "tail [-n N] [file]...",
+            summary: "Print the last lines of input",
+            handler: cmd_tail,
+        },
+        Command {
+            name: "wc",
+            aliases: &[],
+            usage: "wc [-lwc] [file]...",
+            summary: "Count lines, words and bytes",
+            handler: cmd_wc,
+        },
+        Command {
+            name: "sort",
+            aliases: &[],
+            usage: "sort [-rnu] [file]...",
+            summary: "Sort lines of input",
+            handler: cmd_sort,
+        },
+        Command {
+            name: "uniq",
+            aliases: &[],
+            usage: "uniq [-cd] [file]...",
+            summary: "Collapse repeated adjacent lines",
+            handler: cmd_uniq,
+        },
+        Command {
+            name: "hexdump",
+            aliases: &[],
+            usage: "hexdump [-C] [file]...",
+            summary: "Show file bytes in hexadecimal",
+            handler: cmd_hexdump,
+        },
+    ]);
+}
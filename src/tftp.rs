@@ -0,0 +1,311 @@
+use crate::commands::{self, Command};
+use crate::io::{Io, Sink};
+use crate::net::{self, Ipv4Addr};
+use crate::file_system;
+
+const TFTP_PORT: u16 = 69;
+const BLOCK_SIZE: usize = 512;
+const TIMEOUT_MS: u64 = 1000;
+const MAX_RETRIES: usize = 5;
+
+const OP_RRQ: u16 = 1;
+const OP_WRQ: u16 = 2;
+const OP_DATA: u16 = 3;
+const OP_ACK: u16 = 4;
+const OP_ERROR: u16 = 5;
+
+const PACKET_SIZE: usize = 4 + BLOCK_SIZE;
+
+struct Socket(usize);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        net::udp_close(self.0);
+    }
+}
+
+fn error_message(code: u16) -> &'static str {
+    match code {
+        1 => "Server: file not found",
+        2 => "Server: access violation",
+        3 => "Server: disk full",
+        4 => "Server: illegal TFTP operation",
+        5 => "Server: unknown transfer ID",
+        6 => "Server: file already exists",
+        7 => "Server: no such user",
+        _ => "Server: transfer failed",
+    }
+}
+
+fn build_request(opcode: u16, filename: &str, packet: &mut [u8; PACKET_SIZE]) -> Result<usize, &'static str> {
+    let mode = b"octet";
+    let len = 2 + filename.len() + 1 + mode.len() + 1;
+    if filename.is_empty() || len > packet.len() {
+        return Err("Invalid file name");
+    }
+    packet[0..2].copy_from_slice(&opcode.to_be_bytes());
+    packet[2..2 + filename.len()].copy_from_slice(filename.as_bytes());
+    packet[2 + filename.len()] = 0;
+    let mode_start = 3 + filename.len();
+    packet[mode_start..mode_start + mode.len()].copy_from_slice(mode);
+    packet[mode_start + mode.len()] = 0;
+    Ok(len)
+}
+
+fn send_error(socket: &Socket, server: Ipv4Addr, port: u16, code: u16, message: &str) {
+    let mut packet = [0u8; 64];
+    let len = (4 + message.len() + 1).min(packet.len());
+    packet[0..2].copy_from_slice(&OP_ERROR.to_be_bytes());
+    packet[2..4].copy_from_slice(&code.to_be_bytes());
+    packet[4..len - 1].copy_from_slice(&message.as_bytes()[..len - 5]);
+    let _ = net::udp_send_to(socket.0, server, port, &packet[..len]);
+}
+
+fn send_ack(socket: &Socket, server: Ipv4Addr, port: u16, block: u16) -> Result<(), &'static str> {
+    let mut packet = [0u8; 4];
+    packet[0..2].copy_from_slice(&OP_ACK.to_be_bytes());
+    packet[2..4].copy_from_slice(&block.to_be_bytes());
+    net::udp_send_to(socket.0, server, port, &packet)
+}
+
+pub fn get(server: Ipv4Addr, remote: &str, buf: &mut [u8]) -> Result<usize, &'static str> {
+    let socket = Socket(net::udp_bind(0)?);
+
+    let mut request = [0u8; PACKET_SIZE];
+    let request_len = build_request(OP_RRQ, remote, &mut request)?;
+
+    let mut last_packet = [0u8; PACKET_SIZE];
+    last_packet[..request_len].copy_from_slice(&request[..request_len]);
+    let mut last_len = request_len;
+    let mut last_port = TFTP_PORT;
+    net::udp_send_to(socket.0, server, last_port, &last_packet[..last_len])?;
+
+    let mut server_port = None;
+    let mut expected_block: u16 = 1;
+    let mut received = 0usize;
+    let mut retries = 0;
+    let mut packet = [0u8; PACKET_SIZE];
+
+    loop {
+        let (len, src_ip, src_port) = match net::udp_recv_from(socket.0, &mut packet, TIMEOUT_MS) {
+            Ok(result) => result,
+            Err(_) => {
+                retries += 1;
+                if retries > MAX_RETRIES {
+                    return Err("Transfer timed out");
+                }
+                net::udp_send_to(socket.0, server, last_port, &last_packet[..last_len])?;
+                continue;
+            }
+        };
+
+        if src_ip != server || len < 4 {
+            continue;
+        }
+        match server_port {
+            Some(port) if port != src_port => {
+                send_error(&socket, src_ip, src_port, 5, "Unknown transfer ID");
+                continue;
+            }
+            None => server_port = Some(src_port),
+            _ => {}
+        }
+
+        let opcode = u16::from_be_bytes([packet[0], packet[1]]);
+        let block = u16::from_be_bytes([packet[2], packet[3]]);
+        match opcode {
+            OP_DATA if block == expected_block => {
+                retries = 0;
+                let data = &packet[4..len];
+                if received + data.len() > buf.len() {
+                    send_error(&socket, server, src_port, 3, "File too large");
+                    return Err("File too large for the file system");
+                }
+                buf[received..received + data.len()].copy_from_slice(data);
+                received += data.len();
+
+                send_ack(&socket, server, src_port, block)?;
+                last_packet[0..2].copy_from_slice(&OP_ACK.to_be_bytes());
+                last_packet[2..4].copy_from_slice(&block.to_be_bytes());
+                last_len = 4;
+                last_port = src_port;
+
+                if data.len() < BLOCK_SIZE {
+                    return Ok(received);
+                }
+                expected_block = expected_block.wrapping_add(1);
+            }
+            OP_DATA if block == expected_block.wrapping_sub(1) => {
+                send_ack(&socket, server, src_port, block)?;
+            }
+            OP_ERROR => return Err(error_message(block)),
+            _ => {}
+        }
+    }
+}
+
+pub fn put(server: Ipv4Addr, remote: &str, data: &[u8]) -> Result<(), &'static str> {
+    let socket = Socket(net::udp_bind(0)?);
+
+    let mut last_packet = [0u8; PACKET_SIZE];
+    let mut last_len = build_request(OP_WRQ, remote, &mut last_packet)?;
+    let mut last_port = TFTP_PORT;
+    net::udp_send_to(socket.0, server, last_port, &last_packet[..last_len])?;
+
+    let mut server_port = None;
+    let mut acked_block: u16 = 0;
+    let mut sent = 0usize;
+    let mut finished = false;
+    let mut retries = 0;
+    let mut packet = [0u8; PACKET_SIZE];
+
+    loop {
+        let (len, src_ip, src_port) = match net::udp_recv_from(socket.0, &mut packet, TIMEOUT_MS) {
+            Ok(result) => result,
+            Err(_) => {
+                retries += 1;
+                if retries > MAX_RETRIES {
+                    return Err("Transfer timed out");
+                }
+                net::udp_send_to(socket.0, server, last_port, &last_packet[..last_len])?;
+                continue;
+            }
+        };
+
+        if src_ip != server || len < 4 {
+            continue;
+        }
+        match server_port {
+            Some(port) if port != src_port => {
+                send_error(&socket, src_ip, src_port, 5, "Unknown transfer ID");
+                continue;
+            }
+            None => server_port = Some(src_port),
+            _ => {}
+        }
+
+        let opcode = u16::from_be_bytes([packet[0], packet[1]]);
+        let block = u16::from_be_bytes([packet[2], packet[3]]);
+        match opcode {
+            OP_ACK if block == acked_block => {
+                retries = 0;
+                if finished {
+                    return Ok(());
+                }
+
+                let chunk_len = (data.len() - sent).min(BLOCK_SIZE);
+                acked_block = acked_block.wrapping_add(1);
+                last_packet[0..2].copy_from_slice(&OP_DATA.to_be_bytes());
+                last_packet[2..4].copy_from_slice(&acked_block.to_be_bytes());
+                last_packet[4..4 + chunk_len].copy_from_slice(&data[sent..sent + chunk_len]);
+                last_len = 4 + chunk_len;
+                last_port = src_port;
+                sent += chunk_len;
+                finished = chunk_len < BLOCK_SIZE;
+
+                net::udp_send_to(socket.0, server, last_port, &last_packet[..last_len])?;
+            }
+            OP_ERROR => return Err(error_message(block)),
+            _ => {}
+        }
+    }
+}
+
+fn cmd_tftp(io: &mut Io, argv: &[&str]) -> i32 {
+    let usage = "Usage: tftp [server] get <remote> [local] | tftp [server] put <local> [remote]\n";
+    let config = match net::config() {
+        Some(config) => config,
+        None => {
+            io.write_string("Network is down (no RTL8139 NIC found).\n");
+            return 1;
+        }
+    };
+
+    let mut parts = argv[1..].iter().copied();
+    let mut server = config.gateway;
+    let mut action = parts.next();
+    if let Some(ip) = action.and_then(net::parse_ip) {
+        server = ip;
+        action = parts.next();
+    }
+    let first = parts.next();
+    let second = parts.next();
+
+    match (action, first) {
+        (Some("get"), Some(remote)) => {
+            let local = second.unwrap_or(remote);
+            let mut data = [0u8; file_system::MAX_FILE_SIZE];
+            io.write_string("Downloading ");
+            io.write_string(remote);
+            io.write_string("... ");
+            match get(server, remote, &mut data) {
+                Ok(len) => {
+                    match file_system::with_fs_mut(|fs| fs.write_file(local, &data[..len])) {
+                        Ok(_) => {
+                            write!(io, "saved {} bytes to '", len);
+                            io.write_string(local);
+                            io.write_string("'.\n");
+                            0
+                        },
+                        Err(_) => {
+                            io.write_string("Error: Could not write file.\n");
+                            1
+                        }
+                    }
+                }
+                Err(e) => {
+                    io.write_string("failed: ");
+                    io.write_string(e);
+                    io.write_string("\n");
+                    1
+                }
+            }
+        }
+        (Some("put"), Some(local)) => {
+            let remote = second.unwrap_or(local);
+            let mut data = [0u8; file_system::MAX_FILE_SIZE];
+            let len = file_system::with_fs(|fs| {
+                fs.read_file(local).ok().map(|file| {
+                    data[..file.len()].copy_from_slice(file);
+                    file.len()
+                })
+            });
+            let len = match len {
+                Some(len) => len,
+                None => {
+                    io.write_string("Error: File not found.\n");
+                    return 1;
+                }
+            };
+            io.write_string("Uploading ");
+            io.write_string(local);
+            io.write_string("... ");
+            match put(server, remote, &data[..len]) {
+                Ok(_) => {
+                    writeln!(io, "sent {} bytes.", len);
+                    0
+                }
+                Err(e) => {
+                    io.write_string("failed: ");
+                    io.write_string(e);
+                    io.write_string("\n");
+                    1
+                }
+            }
+        }
+        _ => {
+            io.write_string(usage);
+            2
+        }
+    }
+}
+
+pub fn register_commands() {
+    commands::register_all(&[Command {
+        name: "tftp",
+        aliases: &[],
+        usage: "tftp [server] get|put <src> [dst]",
+        summary: "Transfer files with a TFTP server",
+        handler: cmd_tftp,
+    }]);
+}
@@ -0,0 +1,52 @@
+use crate::port;
+
+const PIT_FREQUENCY: u64 = 1_193_182;
+const CALIBRATION_MS: u64 = 10;
+
+static mut TSC_PER_MS: u64 = 0;
+static mut BOOT_TSC: u64 = 0;
+
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe { core::arch::asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags)); }
+    ((high as u64) << 32) | low as u64
+}
+
+pub fn init() {
+    let count = (PIT_FREQUENCY * CALIBRATION_MS / 1000) as u16;
+
+    let gate = port::inb(0x61);
+    port::outb(0x61, (gate & !0x02) | 0x01);
+    port::outb(0x43, 0xB0);
+    port::outb(0x42, count as u8);
+    port::outb(0x42, (count >> 8) as u8);
+
+    let gate = port::inb(0x61) & !0x01;
+    port::outb(0x61, gate);
+    port::outb(0x61, gate | 0x01);
+
+    let start = rdtsc();
+    while port::inb(0x61) & 0x20 == 0 {}
+    let end = rdtsc();
+
+    unsafe {
+        TSC_PER_MS = ((end - start) / CALIBRATION_MS).max(1);
+        BOOT_TSC = start;
+    }
+}
+
+pub fn uptime_ms() -> u64 {
+    let per_ms = unsafe { TSC_PER_MS };
+    if per_ms == 0 {
+        return 0;
+    }
+    (rdtsc() - unsafe { BOOT_TSC }) / per_ms
+}
+
+pub fn sleep_ms(ms: u64) {
+    let start = uptime_ms();
+    while uptime_ms() - start < ms {
+        core::hint::spin_loop();
+    }
+}
@@ -1,5 +1,14 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
 use core::str;
+use spin::Mutex;
 use volatile::Volatile;
+use crate::ansi::{self, Action, Csi};
+use crate::cp437;
+use crate::interrupts;
+use crate::io::{Sink, TextBuffer};
+use crate::memory;
+use crate::port::{inb, outb};
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,9 +24,17 @@ pub enum Color {
 #[repr(transparent)]
 pub struct ColorCode(u8);
 impl ColorCode {
-    pub fn new(foreground: Color, background: Color) -> ColorCode {
+    pub const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    pub const fn from_attribute(attribute: u8) -> ColorCode {
+        ColorCode(attribute)
+    }
+
+    pub const fn attribute(self) -> u8 {
+        self.0
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,13 +44,59 @@ pub struct ScreenChar {
     pub color_code: ColorCode,
 }
 
-pub const BUFFER_HEIGHT: usize = 25;
-pub const BUFFER_WIDTH: usize = 80;
+pub const MAX_WIDTH: usize = 90;
+pub const MAX_HEIGHT: usize = 60;
+
+static mut SCREEN_SIZE: (usize, usize) = (80, 25);
+static mut CHAR_HEIGHT: u8 = 16;
+// Rows kept out of the consoles at the top and bottom of the screen, for the status bar.
+static mut RESERVED_ROWS: (usize, usize) = (0, 0);
+
+/// Columns of the current text mode.
+pub fn width() -> usize {
+    unsafe { SCREEN_SIZE.0 }
+}
+
+/// Rows available to the consoles.
+pub fn height() -> usize {
+    unsafe { SCREEN_SIZE.1 - RESERVED_ROWS.0 - RESERVED_ROWS.1 }
+}
+
+/// Rows of the current text mode, including reserved ones.
+pub fn screen_height() -> usize {
+    unsafe { SCREEN_SIZE.1 }
+}
+
+/// Screen row where the consoles start.
+pub fn first_row() -> usize {
+    unsafe { RESERVED_ROWS.0 }
+}
 
+/// Like set_screen_size, the writers have to be resized afterwards.
+pub fn set_reserved_rows(top: usize, bottom: usize) {
+    unsafe { RESERVED_ROWS = (top, bottom) };
+}
+
+/// Called by the mode switch; the writers still have to be resized.
+pub fn set_screen_size(width: usize, height: usize, char_height: u8) {
+    unsafe {
+        SCREEN_SIZE = (width.min(MAX_WIDTH), height.min(MAX_HEIGHT));
+        CHAR_HEIGHT = char_height;
+    }
+}
+
+// Rows are MAX_WIDTH cells apart in every text mode; the CRTC offset register is set to match.
 #[repr(transparent)]
 pub struct Buffer {
 
-    pub chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    pub chars: [[Volatile<ScreenChar>; MAX_WIDTH]; MAX_HEIGHT],
+}
+
+pub const VGA_ADDRESS: usize = 0xb8000;
+
+/// The VGA text buffer, through the physical memory mapping since larger modes span several pages.
+pub fn hardware_buffer() -> *mut Buffer {
+    memory::phys_to_virt(VGA_ADDRESS as u64) as *mut Buffer
 }
 
 pub struct Writer {
@@ -41,52 +104,209 @@ pub struct Writer {
     pub column_position: usize,
     pub color_code: ColorCode,
     pub buffer: &'static mut Buffer,
+    scrollback: &'static mut Scrollback,
+    ansi: ansi::Parser,
+    utf8: cp437::Utf8Decoder,
+    saved_position: (usize, usize),
+    origin: usize,
 }
 
-pub fn int_to_string<'a>(mut n: usize, buf: &'a mut [u8]) -> &'a str {
-    if n == 0 {
-        buf[0] = b'0';
-        return unsafe { str::from_utf8_unchecked(&buf[..1]) };
-    }
-    let mut i = 0;
-    while n > 0 {
-        buf[i] = b'0' + (n % 10) as u8;
-        n /= 10;
-        i += 1;
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+const CRTC_CURSOR_START: u8 = 0x0A;
+const CRTC_CURSOR_END: u8 = 0x0B;
+const CRTC_CURSOR_HIGH: u8 = 0x0E;
+const CRTC_CURSOR_LOW: u8 = 0x0F;
+const CURSOR_DISABLE: u8 = 0x20;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Underline,
+    HalfBlock,
+    Block,
+}
+
+impl CursorShape {
+    fn scanlines(self) -> (u8, u8) {
+        let last = unsafe { CHAR_HEIGHT } - 1;
+        match self {
+            CursorShape::Underline => (last - 1, last),
+            CursorShape::HalfBlock => (last / 2 + 1, last),
+            CursorShape::Block => (0, last),
+        }
     }
-    buf[..i].reverse();
-    unsafe { str::from_utf8_unchecked(&buf[..i]) }
 }
 
-pub fn wait_for_enter() {
-    loop {
-        let mut scancode: u8 = 0;
-        unsafe {
-            core::arch::asm!(
-                "in al, 0x60",
-                out("al") scancode,
-                options(nomem, nostack, preserves_flags),
-            );
+static mut CURSOR_SHAPE: CursorShape = CursorShape::Underline;
+static mut CURSOR_SCANLINES: (u8, u8) = (14, 15);
+
+fn crtc_read(register: u8) -> u8 {
+    outb(CRTC_INDEX, register);
+    inb(CRTC_DATA)
+}
+
+fn crtc_write(register: u8, value: u8) {
+    outb(CRTC_INDEX, register);
+    outb(CRTC_DATA, value);
+}
+
+pub const SCROLLBACK_LINES: usize = 500;
+
+const BLANK: ScreenChar = ScreenChar {
+    ascii_character: b' ',
+    color_code: ColorCode::new(Color::White, Color::Black),
+};
+const INDICATOR_COLOR: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+
+pub struct Scrollback {
+    lines: [[ScreenChar; MAX_WIDTH]; SCROLLBACK_LINES],
+    start: usize,
+    count: usize,
+    offset: usize,
+    live: [[ScreenChar; MAX_WIDTH]; MAX_HEIGHT],
+}
+
+impl Scrollback {
+    fn push(&mut self, row: [ScreenChar; MAX_WIDTH]) {
+        let index = (self.start + self.count) % SCROLLBACK_LINES;
+        self.lines[index] = row;
+        if self.count < SCROLLBACK_LINES {
+            self.count += 1;
+        } else {
+            self.start = (self.start + 1) % SCROLLBACK_LINES;
         }
-        if scancode == 0x1C {
-            break;
+    }
+
+    fn row(&self, index: usize) -> &[ScreenChar; MAX_WIDTH] {
+        if index < self.count {
+            &self.lines[(self.start + index) % SCROLLBACK_LINES]
+        } else {
+            &self.live[index - self.count]
         }
     }
 }
 
 impl Writer {
+    pub fn new(buffer: &'static mut Buffer, scrollback: &'static mut Scrollback) -> Writer {
+        Writer {
+            row_position: 0,
+            column_position: 0,
+            color_code: ColorCode::new(Color::White, Color::Black),
+            buffer,
+            scrollback,
+            ansi: ansi::Parser::new(),
+            utf8: cp437::Utf8Decoder::new(),
+            saved_position: (0, 0),
+            origin: first_row(),
+        }
+    }
+
+    /// A cell of the console area, `row` counting from its first row.
+    pub fn cell(&mut self, row: usize, col: usize) -> &mut Volatile<ScreenChar> {
+        &mut self.buffer.chars[self.origin + row][col]
+    }
+
+    pub fn is_visible(&self) -> bool {
+        core::ptr::eq(self.buffer, hardware_buffer())
+    }
+
     pub fn write_byte(&mut self, byte: u8) {
+        self.feed(byte);
+        self.update_cursor();
+    }
+
+    /// Writes a CP437 glyph as is, without UTF-8 decoding or escape sequences.
+    pub fn write_glyph(&mut self, glyph: u8) {
+        self.put_byte(glyph);
+        self.update_cursor();
+    }
+
+    pub fn write_char(&mut self, c: char) {
+        self.write_glyph(cp437::from_char(c));
+    }
+
+    fn feed(&mut self, byte: u8) {
+        match self.ansi.feed(byte) {
+            Action::None => {}
+            Action::Print(byte) => {
+                if let Some(glyph) = self.utf8.feed_glyph(byte) {
+                    self.put_byte(glyph);
+                }
+            }
+            Action::Escape(b'7') => self.saved_position = (self.row_position, self.column_position),
+            Action::Escape(b'8') => (self.row_position, self.column_position) = self.saved_position,
+            Action::Escape(b'c') => {
+                self.ansi.reset();
+                self.color_code = ColorCode::new(Color::White, Color::Black);
+                self.clear_screen();
+            }
+            Action::Escape(_) => {}
+            Action::Csi(csi) => self.execute_csi(&csi),
+        }
+    }
+
+    fn execute_csi(&mut self, csi: &Csi) {
+        self.snap_to_bottom();
+        let (row, col) = (self.row_position, self.column_position);
+        let amount = csi.param(0, 1) as usize;
+        match (csi.private, csi.command) {
+            (false, b'm') => self.color_code = self.ansi.apply_sgr(csi, self.color_code),
+            (false, b'H') | (false, b'f') => {
+                self.row_position = (csi.param(0, 1) as usize - 1).min(height() - 1);
+                self.column_position = (csi.param(1, 1) as usize - 1).min(width() - 1);
+            }
+            (false, b'A') => self.row_position = row.saturating_sub(amount),
+            (false, b'B') => self.row_position = (row + amount).min(height() - 1),
+            (false, b'C') => self.column_position = (col + amount).min(width() - 1),
+            (false, b'D') => self.column_position = col.min(width() - 1).saturating_sub(amount),
+            (false, b'G') => self.column_position = (amount - 1).min(width() - 1),
+            (false, b'J') => match csi.param(0, 0) {
+                0 => {
+                    self.clear_cells(row, col, width());
+                    for r in row + 1..height() {
+                        self.clear_row(r);
+                    }
+                }
+                1 => {
+                    for r in 0..row {
+                        self.clear_row(r);
+                    }
+                    self.clear_cells(row, 0, col + 1);
+                }
+                _ => {
+                    for r in 0..height() {
+                        self.clear_row(r);
+                    }
+                }
+            },
+            (false, b'K') => match csi.param(0, 0) {
+                0 => self.clear_cells(row, col, width()),
+                1 => self.clear_cells(row, 0, col + 1),
+                _ => self.clear_row(row),
+            },
+            (false, b's') => self.saved_position = (row, col),
+            (false, b'u') => (self.row_position, self.column_position) = self.saved_position,
+            (true, b'h') if csi.param(0, 0) == 25 => self.show_cursor(),
+            (true, b'l') if csi.param(0, 0) == 25 => self.disable_cursor(),
+            _ => {}
+        }
+    }
+
+    fn put_byte(&mut self, byte: u8) {
+        self.snap_to_bottom();
         match byte {
             b'\n' => self.new_line(),
             b => {
-                if self.column_position >= BUFFER_WIDTH {
+                if self.column_position >= width() {
                     self.new_line();
                 }
                 let row = self.row_position;
                 let col = self.column_position;
-                self.buffer.chars[row][col].write(ScreenChar {
+                let color_code = self.color_code;
+                self.cell(row, col).write(ScreenChar {
                     ascii_character: b,
-                    color_code: self.color_code,
+                    color_code,
                 });
                 self.column_position += 1;
             }
@@ -95,283 +315,398 @@ impl Writer {
     pub fn write_string(&mut self, s: &str) {
         for byte in s.bytes() {
             match byte {
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                _ => self.write_byte(0xfe),
+                0x20..=0x7e | 0x80..=0xff | b'\n' | ansi::ESC => self.feed(byte),
+                _ => self.put_byte(cp437::UNKNOWN_GLYPH),
             }
         }
+        self.update_cursor();
+    }
+
+    pub fn enable_cursor(&mut self, start: u8, end: u8) {
+        unsafe { CURSOR_SCANLINES = (start, end) };
+        if !self.is_visible() {
+            return;
+        }
+        crtc_write(CRTC_CURSOR_START, (crtc_read(CRTC_CURSOR_START) & 0xC0) | (start & 0x1F));
+        crtc_write(CRTC_CURSOR_END, (crtc_read(CRTC_CURSOR_END) & 0xE0) | (end & 0x1F));
+        self.update_cursor();
+    }
+
+    pub fn disable_cursor(&mut self) {
+        if !self.is_visible() {
+            return;
+        }
+        crtc_write(CRTC_CURSOR_START, CURSOR_DISABLE);
+    }
+
+    pub fn show_cursor(&mut self) {
+        let (start, end) = unsafe { CURSOR_SCANLINES };
+        self.enable_cursor(start, end);
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        unsafe { CURSOR_SHAPE = shape };
+        let (start, end) = shape.scanlines();
+        self.enable_cursor(start, end);
+    }
+
+    pub fn set_cursor_position(&mut self, row: usize, col: usize) {
+        self.row_position = row.min(height() - 1);
+        self.column_position = col.min(width());
+        self.update_cursor();
+    }
+
+    pub fn update_cursor(&mut self) {
+        if !self.is_visible() {
+            return;
+        }
+        let position = (self.origin + self.row_position) * MAX_WIDTH + self.column_position.min(width() - 1);
+        crtc_write(CRTC_CURSOR_HIGH, (position >> 8) as u8);
+        crtc_write(CRTC_CURSOR_LOW, position as u8);
     }
     fn new_line(&mut self) {
-        if self.row_position + 1 < BUFFER_HEIGHT {
+        if self.row_position + 1 < height() {
             self.row_position += 1;
             self.column_position = 0;
             return;
         }
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let ch = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(ch);
+        self.scroll_up();
+        self.column_position = 0;
+        self.row_position = height() - 1;
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.snap_to_bottom();
+        self.scroll_rows(1, height());
+        self.clear_row(height() - 1);
+    }
+
+    /// Moves rows `count..rows` up by `count`, pushing the top ones into the scrollback.
+    fn scroll_rows(&mut self, count: usize, rows: usize) {
+        for row in 0..count {
+            let mut line = [BLANK; MAX_WIDTH];
+            for col in 0..width() {
+                line[col] = self.cell(row, col).read();
+            }
+            self.scrollback.push(line);
+        }
+        for row in count..rows {
+            for col in 0..MAX_WIDTH {
+                let ch = self.cell(row, col).read();
+                self.cell(row - count, col).write(ch);
             }
         }
-        self.clear_row(BUFFER_HEIGHT - 1);
-        self.column_position = 0;
-        self.row_position = BUFFER_HEIGHT - 1;
     }
 
     fn clear_row(&mut self, row: usize) {
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(ScreenChar {
-                ascii_character: b' ',
-                color_code: self.color_code,
-            });
+        self.clear_cells(row, 0, width());
+    }
+
+    fn clear_cells(&mut self, row: usize, from: usize, to: usize) {
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+        for col in from..to.min(width()) {
+            self.cell(row, col).write(blank);
         }
     }
 
-    pub fn clear_screen(&mut self) {
-        for row in 0..BUFFER_HEIGHT {
-            self.clear_row(row);
+    pub fn scroll_back(&mut self, lines: usize) {
+        if self.scrollback.offset == 0 {
+            for row in 0..height() {
+                for col in 0..width() {
+                    self.scrollback.live[row][col] = self.cell(row, col).read();
+                }
+            }
         }
-        self.row_position = 0;
-        self.column_position = 0;
+        self.scrollback.offset = (self.scrollback.offset + lines).min(self.scrollback.count);
+        if self.scrollback.offset > 0 {
+            self.disable_cursor();
+        }
+        self.show_scrollback();
     }
-}
 
-pub fn color_test() {
-    let mut writer = Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::White, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    };
+    pub fn scroll_forward(&mut self, lines: usize) {
+        if self.scrollback.offset > lines {
+            self.scrollback.offset -= lines;
+            self.show_scrollback();
+        } else {
+            self.snap_to_bottom();
+        }
+    }
 
-    for bg in 0..=15 {
-        for fg in 0..=15 {
-            writer.color_code = ColorCode::new(
-                unsafe { core::mem::transmute(fg as u8) },
-                unsafe { core::mem::transmute(bg as u8) },
-            );
-            writer.write_string("X");
+    pub fn snap_to_bottom(&mut self) {
+        if self.scrollback.offset == 0 {
+            return;
+        }
+        self.scrollback.offset = 0;
+        for row in 0..height() {
+            for col in 0..width() {
+                let ch = self.scrollback.live[row][col];
+                self.cell(row, col).write(ch);
+            }
         }
-        writer.write_string("\n");
+        self.show_cursor();
     }
-}
 
-pub fn ascii_test() {
-    let mut writer = Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::LightGray, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    };
+    fn show_scrollback(&mut self) {
+        let history = &*self.scrollback;
+        if history.offset == 0 {
+            return;
+        }
+        let top = history.count - history.offset;
+        for row in 0..height() {
+            let line = history.row(top + row);
+            for col in 0..width() {
+                self.buffer.chars[self.origin + row][col].write(line[col]);
+            }
+        }
 
-    for c in 0u8..=255 {
-        writer.write_byte(c);
+        let mut label = TextBuffer::<32>::new();
+        write!(label, "[Scrollback: -{} lines]", history.offset);
+        let label = label.as_bytes();
+        let len = label.len();
+        for (i, &byte) in label.iter().enumerate() {
+            self.cell(0, width() - len + i).write(ScreenChar {
+                ascii_character: byte,
+                color_code: INDICATOR_COLOR,
+            });
+        }
     }
-}
-
-pub fn keyboard_test() {
-    let mut writer = Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::LightGreen, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    };
 
-    writer.write_string("Press keys to see their scancodes:\n");
+    pub fn clear_screen(&mut self) {
+        self.snap_to_bottom();
+        for row in 0..height() {
+            self.clear_row(row);
+        }
+        self.set_cursor_position(0, 0);
+    }
 
-    loop {
-        let mut scancode: u8 = 0;
-        unsafe {
-            core::arch::asm!(
-                "in al, 0x60",
-                out("al") scancode,
-                options(nomem, nostack, preserves_flags),
-            );
+    /// Fits the screen to a new text mode size. Rows below the new bottom scroll into the
+    /// scrollback and cells outside the screen are blanked, so growing again shows no leftovers.
+    pub fn resize(&mut self) {
+        self.snap_to_bottom();
+        let origin = first_row();
+        if origin != self.origin {
+            self.move_screen(self.origin, origin);
+            self.origin = origin;
+        }
+        let (width, height) = (width(), height());
+        if self.row_position >= height {
+            let count = self.row_position + 1 - height;
+            self.scroll_rows(count, MAX_HEIGHT - origin);
+            self.row_position -= count;
+        }
+        for row in 0..MAX_HEIGHT {
+            let from = if row >= origin && row < origin + height { width } else { 0 };
+            for col in from..MAX_WIDTH {
+                self.buffer.chars[row][col].write(BLANK);
+            }
         }
-        writer.write_string("Scancode: ");
-        let mut num_buf = [0u8; 20];
-        let s = int_to_string(scancode as usize, &mut num_buf);
-        writer.write_string(s);
-        writer.write_string("\n");
-        if scancode == 0x1C {
-            break;
+        self.column_position = self.column_position.min(width);
+        self.saved_position = (self.saved_position.0.min(height - 1), self.saved_position.1.min(width - 1));
+        self.set_cursor_shape(unsafe { CURSOR_SHAPE });
+    }
+
+    /// Moves the whole screen so that row `from` ends up at row `to`.
+    fn move_screen(&mut self, from: usize, to: usize) {
+        let rows = MAX_HEIGHT - from.max(to);
+        let mut move_row = |row: usize| {
+            for col in 0..MAX_WIDTH {
+                let ch = self.buffer.chars[from + row][col].read();
+                self.buffer.chars[to + row][col].write(ch);
+            }
+        };
+        if to > from {
+            (0..rows).rev().for_each(&mut move_row);
+        } else {
+            (0..rows).for_each(&mut move_row);
         }
     }
 }
 
-pub fn math_test() {
-    let mut writer = Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::Cyan, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    };
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
 
-    writer.write_string("Basic Math Tests:\n");
+/// Handle to whichever virtual console is on screen.
+pub struct ActiveConsole;
 
-    let a = 5;
-    let b = 3;
+impl Deref for ActiveConsole {
+    type Target = Writer;
 
-    writer.write_string("Addition: ");
-    let sum = a + b;
-    let mut num_buf = [0u8; 20];
-    let s = int_to_string(sum, &mut num_buf);
-    writer.write_string(s);
-    writer.write_string("\n");
+    fn deref(&self) -> &Writer {
+        crate::console::writer()
+    }
+}
 
-    writer.write_string("Subtraction: ");
-    let diff = a - b;
-    let s = int_to_string(diff, &mut num_buf);
-    writer.write_string(s);
-    writer.write_string("\n");
+impl DerefMut for ActiveConsole {
+    fn deref_mut(&mut self) -> &mut Writer {
+        crate::console::writer()
+    }
+}
 
-    writer.write_string("Multiplication: ");
-    let prod = a * b;
-    let s = int_to_string(prod, &mut num_buf);
-    writer.write_string(s);
-    writer.write_string("\n");
+pub static WRITER: Mutex<ActiveConsole> = Mutex::new(ActiveConsole);
 
-    writer.write_string("Division: ");
-    let quot = a / b;
-    let s = int_to_string(quot, &mut num_buf);
-    writer.write_string(s);
-    writer.write_string("\n");
+/// Runs `f` with the active console locked and interrupts off, so an interrupt handler that
+/// prints can't spin on a lock the code it interrupted is holding.
+pub fn with_writer<R>(f: impl FnOnce(&mut Writer) -> R) -> R {
+    interrupts::without_interrupts(|| f(&mut WRITER.lock()))
 }
 
-pub fn panic_test() {
-    panic!("This is a test panic!");
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
 }
 
-pub fn hex_to_string(mut num: u32, buffer: &mut [u8]) -> &str {
-    const HEX_CHARS: &[u8] = b"0123456789ABCDEF";
-    let mut i = 0;
-    if num == 0 {
-        buffer[0] = b'0';
-        return unsafe { core::str::from_utf8_unchecked(&buffer[0..1]) };
-    }
-    let mut temp_buf = [0u8; 8];
-    let mut temp_len = 0;
-    while num > 0 && temp_len < temp_buf.len() {
-        temp_buf[temp_len] = HEX_CHARS[(num & 0xF) as usize];
-        num >>= 4;
-        temp_len += 1;
-    }
-    for j in 0..temp_len {
-        if i < buffer.len() {
-            buffer[i] = temp_buf[temp_len - 1 - j];
-            i += 1;
-        }
-    }
-    unsafe { core::str::from_utf8_unchecked(&buffer[0..i]) }
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
-pub fn read_line(writer: &mut Writer, buffer: &mut [u8]) -> Result<usize, &'static str> {
-    let mut i = 0usize;
-    loop {
-        let mut scancode: u8 = 0;
-        unsafe {
-            core::arch::asm!(
-                "in al, 0x60",
-                out("al") scancode,
-                options(nomem, nostack, preserves_flags),
-            );
-        }
-        if (scancode & 0x80) != 0 {
-            continue;
-        }
-        if scancode == 0x1C {
-            writer.write_string("\n");
-            break;
-        }
-        if scancode == 0x0E {
-            if i > 0 {
-                i -= 1;
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    with_writer(|writer| writer.write_fmt(args));
+}
 
-                writer.write_string("\u{8} \u{8}");
-            }
-            continue;
-        }
-        let c = match scancode {
-            0x02 => b'1', 0x03 => b'2', 0x04 => b'3', 0x05 => b'4',
-            0x06 => b'5', 0x07 => b'6', 0x08 => b'7', 0x09 => b'8',
-            0x0A => b'9', 0x0B => b'0', 0x0C => b'-', 0x0D => b'=',
-            0x10 => b'q', 0x11 => b'w', 0x12 => b'e', 0x13 => b'r',
-            0x14 => b't', 0x15 => b'y', 0x16 => b'u', 0x17 => b'i',
-            0x18 => b'o', 0x19 => b'p', 0x1A => b'[', 0x1B => b']',
-            0x1E => b'a', 0x1F => b's', 0x20 => b'd', 0x21 => b'f',
-            0x22 => b'g', 0x23 => b'h', 0x24 => b'j', 0x25 => b'k',
-            0x26 => b'l', 0x27 => b';', 0x28 => b'\'', 0x29 => b'`',
-            0x2C => b'z', 0x2D => b'x', 0x2E => b'c', 0x2F => b'v',
-            0x30 => b'b', 0x31 => b'n', 0x32 => b'm', 0x33 => b',',
-            0x34 => b'.', 0x35 => b'/', 0x39 => b' ', 
-            _ => 0,
-        };
-        if c != 0 {
-            if i < buffer.len() {
-                buffer[i] = c;
-                i += 1;
-                writer.write_byte(c);
-            } else {
+pub fn color_test() {
+    with_writer(|writer| {
+        for bg in 0..=15 {
+            for fg in 0..=15 {
+                writer.color_code = ColorCode::new(
+                    unsafe { core::mem::transmute(fg as u8) },
+                    unsafe { core::mem::transmute(bg as u8) },
+                );
+                writer.write_string("X");
             }
+            writer.write_string("\n");
         }
-    }
-    Ok(i)
+        writer.color_code = ColorCode::new(Color::White, Color::Black);
+    });
 }
 
-pub fn file_system_test() {
-    let mut writer = Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::White, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    };
-
-    writer.write_string("File System Test:\n");
-
-    crate::file_system::with_fs_mut(|fs| {
-        match fs.write_file("test.txt", b"Hello, World!") {
-            Ok(_) => writer.write_string("✓ File write successful\n"),
-            Err(_) => writer.write_string("✗ File write failed\n"),
+pub fn ascii_test() {
+    with_writer(|writer| {
+        writer.color_code = ColorCode::new(Color::LightGray, Color::Black);
+        for c in 0u8..=255 {
+            writer.write_glyph(c);
         }
+        writer.write_byte(b'\n');
+        writer.color_code = ColorCode::new(Color::White, Color::Black);
     });
+}
 
-    crate::file_system::with_fs(|fs| {
-        match fs.read_file("test.txt") {
-            Ok(data) => {
-                writer.write_string("✓ File read successful: ");
-                for &byte in data {
-                    writer.write_byte(byte);
-                }
-                writer.write_string("\n");
-            },
-            Err(_) => writer.write_string("✗ File read failed\n"),
-        }
+pub fn math_test() {
+    with_writer(|writer| {
+        writer.color_code = ColorCode::new(Color::Cyan, Color::Black);
+        writer.write_string("Basic Math Tests:\n");
+
+        let a: i32 = 5;
+        let b: i32 = 3;
+
+        writeln!(writer, "Addition: {}", a + b);
+        writeln!(writer, "Subtraction: {} (reversed: {})", a - b, b - a);
+        writeln!(writer, "Multiplication: {}", a * b);
+        writeln!(writer, "Division: {}", a / b);
+        writer.color_code = ColorCode::new(Color::White, Color::Black);
     });
+}
 
-    crate::file_system::with_fs(|fs| {
-        let files = fs.list_all_files();
-        let mut count = 0;
-        writer.write_string("✓ Files in system: ");
-        for file_option in &files {
-            if let Some(file_name) = file_option {
-                if let Ok(name_str) = core::str::from_utf8(file_name) {
-                    if count > 0 { writer.write_string(", "); }
-                    writer.write_string(name_str);
-                    count += 1;
+pub fn panic_test() {
+    panic!("This is a test panic!");
+}
+
+pub fn file_system_test() {
+    with_writer(|writer| {
+        writer.write_string("File System Test:\n");
+
+        crate::file_system::with_fs_mut(|fs| {
+            match fs.write_file("test.txt", b"Hello, World!") {
+                Ok(_) => writer.write_string("✓ File write successful\n"),
+                Err(_) => writer.write_string("✗ File write failed\n"),
+            }
+        });
+
+        crate::file_system::with_fs(|fs| {
+            match fs.read_file("test.txt") {
+                Ok(data) => {
+                    writer.write_string("✓ File read successful: ");
+                    for &byte in data {
+                        writer.write_byte(byte);
+                    }
+                    writer.write_string("\n");
+                },
+                Err(_) => writer.write_string("✗ File read failed\n"),
+            }
+        });
+
+        crate::file_system::with_fs(|fs| {
+            let files = fs.list_all_files();
+            let mut count = 0;
+            writer.write_string("✓ Files in system: ");
+            for file_option in &files {
+                if let Some(file_name) = file_option {
+                    if let Ok(name_str) = core::str::from_utf8(file_name) {
+                        if count > 0 { writer.write_string(", "); }
+                        writer.write_string(name_str);
+                        count += 1;
+                    }
                 }
             }
-        }
-        if count == 0 {
-            writer.write_string("(none)");
-        }
-        writer.write_string("\n");
+            if count == 0 {
+                writer.write_string("(none)");
+            }
+            writer.write_string("\n");
+        });
+
+        crate::file_system::with_fs_mut(|fs| {
+            match fs.delete_file("test.txt") {
+                Ok(_) => writer.write_string("✓ File deletion successful\n"),
+                Err(_) => writer.write_string("✗ File deletion failed\n"),
+            }
+        });
+
+        writer.write_string("File system test completed.\n\n");
     });
+}
+
+pub fn network_test() {
+    crate::println!("Network Test:");
 
-    crate::file_system::with_fs_mut(|fs| {
-        match fs.delete_file("test.txt") {
-            Ok(_) => writer.write_string("✓ File deletion successful\n"),
-            Err(_) => writer.write_string("✗ File deletion failed\n"),
+    let config = match crate::net::config() {
+        Some(config) => config,
+        None => {
+            crate::println!("✗ No network interface (start QEMU with -nic user,model=rtl8139)\n");
+            return;
         }
-    });
+    };
+
+    match crate::net::resolve(config.gateway, 2000) {
+        Ok(mac) => with_writer(|writer| {
+            writer.write_string("✓ ARP resolved gateway to ");
+            crate::net::write_mac(writer, mac);
+            writer.write_string("\n");
+        }),
+        Err(e) => crate::println!("✗ ARP failed: {}", e),
+    }
+
+    match crate::net::ping(config.gateway, 1, 2000) {
+        Ok((rtt, _)) => crate::println!("✓ Gateway answered ping in {}ms", rtt),
+        Err(e) => crate::println!("✗ Ping failed: {}", e),
+    }
+
+    match crate::net::udp_bind(0) {
+        Ok(socket) => {
+            crate::println!("✓ UDP socket bound to port {}", crate::net::udp_local_port(socket));
+            crate::net::udp_close(socket);
+        },
+        Err(e) => crate::println!("✗ UDP bind failed: {}", e),
+    }
 
-    writer.write_string("File system test completed.\n\n");
+    crate::println!("Network test completed.\n");
 }
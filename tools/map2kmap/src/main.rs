@@ -0,0 +1,404 @@
+//! Converts a Linux console keymap (the `.map` files used by `loadkeys`) into the keymap format
+//! read by Anomia OS. Linux keycodes 1 to 88 are the same as scancode set 1, so keys carry over
+//! by number. Only the plain, Shift and AltGr levels are kept.
+//!
+//!     map2kmap /usr/share/keymaps/i386/qwerty/es.map es.kmap
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const SHIFT: u32 = 1;
+const ALTGR: u32 = 2;
+const KEY_COUNT: u32 = 128;
+
+const ASCII_NAMES: [&str; 95] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "apostrophe",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "minus", "period", "slash", "zero",
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "colon", "semicolon",
+    "less", "equal", "greater", "question", "at", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J",
+    "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "bracketleft",
+    "backslash", "bracketright", "asciicircum", "underscore", "grave", "a", "b", "c", "d", "e", "f",
+    "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y",
+    "z", "braceleft", "bar", "braceright", "asciitilde",
+];
+
+// U+00A0 to U+00FF.
+const LATIN1_NAMES: [&str; 96] = [
+    "nobreakspace", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "diaeresis", "copyright", "ordfeminine", "guillemotleft", "notsign", "hyphen", "registered",
+    "macron", "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph",
+    "periodcentered", "cedilla", "onesuperior", "masculine", "guillemotright", "onequarter",
+    "onehalf", "threequarters", "questiondown", "Agrave", "Aacute", "Acircumflex", "Atilde",
+    "Adiaeresis", "Aring", "AE", "Ccedilla", "Egrave", "Eacute", "Ecircumflex", "Ediaeresis",
+    "Igrave", "Iacute", "Icircumflex", "Idiaeresis", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocircumflex", "Otilde", "Odiaeresis", "multiply", "Ooblique", "Ugrave", "Uacute",
+    "Ucircumflex", "Udiaeresis", "Yacute", "THORN", "ssharp", "agrave", "aacute", "acircumflex",
+    "atilde", "adiaeresis", "aring", "ae", "ccedilla", "egrave", "eacute", "ecircumflex",
+    "ediaeresis", "igrave", "iacute", "icircumflex", "idiaeresis", "eth", "ntilde", "ograve",
+    "oacute", "ocircumflex", "otilde", "odiaeresis", "division", "oslash", "ugrave", "uacute",
+    "ucircumflex", "udiaeresis", "yacute", "thorn", "ydiaeresis",
+];
+
+const ALIASES: [(&str, char); 8] = [
+    ("quoteright", '\''),
+    ("quoteleft", '`'),
+    ("Oslash", 'Ø'),
+    ("ordmasculine", 'º'),
+    ("euro", '€'),
+    ("EuroSign", '€'),
+    ("dieresis", '¨'),
+    ("ydieresis", 'ÿ'),
+];
+
+// The accents the kernel can compose.
+const DEAD_KEYS: [(&str, char); 5] = [
+    ("dead_grave", '`'),
+    ("dead_acute", '´'),
+    ("dead_circumflex", '^'),
+    ("dead_tilde", '~'),
+    ("dead_diaeresis", '¨'),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Symbol {
+    Char(char),
+    Dead(char),
+}
+
+#[derive(Default, Clone, Copy)]
+struct Key {
+    plain: Option<Symbol>,
+    shift: Option<Symbol>,
+    altgr: Option<Symbol>,
+    caps: bool,
+}
+
+struct Converter {
+    keys: BTreeMap<u32, Key>,
+    // Modifier mask of each column after the `=`, from the `keymaps` line.
+    columns: Vec<u32>,
+}
+
+fn named_char(name: &str) -> Option<char> {
+    if let Some(index) = ASCII_NAMES.iter().position(|&n| n == name) {
+        return char::from_u32(0x20 + index as u32);
+    }
+    if let Some(index) = LATIN1_NAMES.iter().position(|&n| n == name) {
+        return char::from_u32(0xA0 + index as u32);
+    }
+    if let Some(&(_, c)) = ALIASES.iter().find(|(n, _)| *n == name) {
+        return Some(c);
+    }
+    // Older maps spell the umlauts "dieresis".
+    if let Some(index) = LATIN1_NAMES.iter().position(|&n| n.replace("diaeresis", "dieresis") == name) {
+        return char::from_u32(0xA0 + index as u32);
+    }
+    let hex = name.strip_prefix("U+").or_else(|| name.strip_prefix("u+"))?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// A keysym and whether it is a letter Caps Lock applies to (written with a leading `+`).
+fn parse_symbol(token: &str) -> (Option<Symbol>, bool) {
+    let (name, caps) = match token.strip_prefix('+') {
+        Some(name) => (name, true),
+        None => (token, false),
+    };
+    if let Some(&(_, accent)) = DEAD_KEYS.iter().find(|(n, _)| *n == name) {
+        return (Some(Symbol::Dead(accent)), false);
+    }
+    match named_char(name) {
+        Some(c) => (Some(Symbol::Char(c)), caps || c.is_alphabetic()),
+        None => (None, false),
+    }
+}
+
+fn modifier_bit(word: &str) -> Option<u32> {
+    match word {
+        "plain" => Some(0),
+        "shift" => Some(SHIFT),
+        "altgr" => Some(ALTGR),
+        "control" => Some(4),
+        "alt" => Some(8),
+        "shiftl" => Some(16),
+        "shiftr" => Some(32),
+        "ctrll" => Some(64),
+        "ctrlr" => Some(128),
+        "capsshift" => Some(256),
+        _ => None,
+    }
+}
+
+/// Parses `0-2,4-6,8` into the list of modifier masks it names.
+fn parse_keymaps(list: &str) -> Option<Vec<u32>> {
+    let mut columns = Vec::new();
+    for range in list.split(',') {
+        let (start, end): (u32, u32) = match range.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let value = range.trim().parse().ok()?;
+                (value, value)
+            }
+        };
+        columns.extend(start..=end);
+    }
+    Some(columns)
+}
+
+fn uppercase(symbol: Option<Symbol>) -> Option<Symbol> {
+    match symbol {
+        Some(Symbol::Char(c)) => Some(Symbol::Char(c.to_uppercase().next().unwrap_or(c))),
+        other => other,
+    }
+}
+
+impl Converter {
+    fn new() -> Self {
+        Converter { keys: BTreeMap::new(), columns: vec![0, SHIFT, ALTGR, SHIFT | ALTGR] }
+    }
+
+    fn set(&mut self, keycode: u32, mask: u32, symbol: Option<Symbol>, caps: bool) {
+        if keycode == 0 || keycode >= KEY_COUNT {
+            return;
+        }
+        let key = self.keys.entry(keycode).or_default();
+        match mask {
+            0 => {
+                key.plain = symbol;
+                key.caps = caps;
+            }
+            SHIFT => key.shift = symbol,
+            ALTGR => key.altgr = symbol,
+            _ => {}
+        }
+    }
+
+    fn keycode_line(&mut self, modifiers: &[&str], keycode: u32, symbols: &[&str]) -> Result<(), String> {
+        if !modifiers.is_empty() {
+            let mut mask = 0;
+            for word in modifiers {
+                mask |= modifier_bit(word).ok_or_else(|| format!("unknown modifier {}", word))?;
+            }
+            let (symbol, caps) = parse_symbol(symbols.first().ok_or("missing keysym")?);
+            self.set(keycode, mask, symbol, caps);
+            return Ok(());
+        }
+
+        if let [single] = symbols {
+            // One keysym stands for every level; letters get their capital with Shift.
+            let (symbol, caps) = parse_symbol(single);
+            self.set(keycode, 0, symbol, caps);
+            self.set(keycode, SHIFT, if caps { uppercase(symbol) } else { symbol }, caps);
+            return Ok(());
+        }
+
+        for (&mask, token) in self.columns.clone().iter().zip(symbols) {
+            let (symbol, caps) = parse_symbol(token);
+            self.set(keycode, mask, symbol, caps);
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: &str, dir: &Path) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => Ok(()),
+            ["include", name] => self.include(name.trim_matches('"'), dir),
+            ["keymaps", list] => {
+                self.columns = parse_keymaps(list).ok_or_else(|| format!("bad keymaps line: {}", line))?;
+                Ok(())
+            }
+            _ => {
+                let Some(keyword) = words.iter().position(|&w| w == "keycode") else {
+                    // strings, compose, charset and the like mean nothing here.
+                    return Ok(());
+                };
+                let keycode = words.get(keyword + 1).and_then(|w| w.parse().ok()).ok_or_else(|| format!("bad keycode line: {}", line))?;
+                if words.get(keyword + 2) != Some(&"=") {
+                    return Err(format!("bad keycode line: {}", line));
+                }
+                self.keycode_line(&words[..keyword], keycode, &words[keyword + 3..])
+            }
+        }
+    }
+
+    fn include(&mut self, name: &str, dir: &Path) -> Result<(), String> {
+        let candidates = [name.to_string(), format!("{}.map", name), format!("{}.inc", name)];
+        let path = candidates
+            .iter()
+            .flat_map(|candidate| [dir.join(candidate), dir.join("..").join("include").join(candidate)])
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("cannot find include \"{}\" next to the keymap", name))?;
+        self.file(&path)
+    }
+
+    fn file(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+        let mut pending = String::new();
+        for (index, raw) in text.lines().enumerate() {
+            let line = raw.split(['#', '!']).next().unwrap_or("");
+            // A trailing backslash continues the line.
+            if let Some(start) = line.trim_end().strip_suffix('\\') {
+                pending.push_str(start);
+                pending.push(' ');
+                continue;
+            }
+            pending.push_str(line);
+            let full = std::mem::take(&mut pending);
+            self.line(&full, &dir).map_err(|e| format!("{}:{}: {}", path.display(), index + 1, e))?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, name: &str, source: &str) -> String {
+        let mut out = format!("# Converted from {} by map2kmap.\nname {}\n", source, name);
+        for (&keycode, key) in &self.keys {
+            if key.plain.is_none() && key.shift.is_none() && key.altgr.is_none() {
+                continue;
+            }
+            let mut line = format!("0x{:02X}  {:<8}{:<8}{:<8}", keycode, format_symbol(key.plain), format_symbol(key.shift), format_symbol(key.altgr));
+            if key.caps {
+                line.push_str("caps");
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn format_char(c: char) -> String {
+    if c.is_whitespace() || c.is_control() {
+        format!("U+{:04X}", c as u32)
+    } else {
+        c.to_string()
+    }
+}
+
+fn format_symbol(symbol: Option<Symbol>) -> String {
+    match symbol {
+        None => "none".to_string(),
+        Some(Symbol::Char(c)) => format_char(c),
+        Some(Symbol::Dead(c)) => format!("dead:{}", format_char(c)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(input) = args.get(1) else {
+        eprintln!("usage: map2kmap <keymap.map> [output.kmap]");
+        return ExitCode::from(2);
+    };
+    let input = Path::new(input);
+
+    let mut converter = Converter::new();
+    if let Err(e) = converter.file(input) {
+        eprintln!("map2kmap: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let name = input.file_stem().and_then(|s| s.to_str()).unwrap_or("custom");
+    let name = name.strip_suffix(".map").unwrap_or(name);
+    let source = input.file_name().and_then(|s| s.to_str()).unwrap_or(name);
+    let output = converter.write(name, source);
+    match args.get(2) {
+        Some(path) => {
+            if let Err(e) = fs::write(path, output) {
+                eprintln!("map2kmap: {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", output),
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+#[path = "../../../src/kmap.rs"]
+#[allow(dead_code)]
+mod kmap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name)
+    }
+
+    #[test]
+    fn converts_sample_map() {
+        let mut converter = Converter::new();
+        converter.file(&testdata("qwerty/sample.map")).unwrap();
+        let expected = fs::read_to_string(testdata("qwerty/sample.kmap")).unwrap();
+        assert_eq!(converter.write("sample", "sample.map"), expected);
+    }
+
+    #[test]
+    fn reports_missing_include() {
+        let mut converter = Converter::new();
+        let error = converter.line("include \"nowhere\"", &testdata("qwerty")).unwrap_err();
+        assert!(error.contains("nowhere"), "{}", error);
+    }
+
+    fn to_symbol(symbol: Option<kmap::KeySymbol>) -> Option<Symbol> {
+        symbol.map(|symbol| match symbol {
+            kmap::KeySymbol::Char(c) => Symbol::Char(c),
+            kmap::KeySymbol::Dead(c) => Symbol::Dead(c),
+        })
+    }
+
+    /// Every built-in keymap parses with the kernel's parser, and comes back the same after
+    /// being written out by map2kmap and parsed again.
+    #[test]
+    fn builtin_keymaps_round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../keymaps");
+        let mut count = 0;
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("kmap") {
+                continue;
+            }
+            let text = fs::read_to_string(&path).unwrap();
+            let mut keymap = kmap::Keymap::new();
+            if let Err((line, e)) = kmap::parse(&text, &mut keymap) {
+                panic!("{}:{}: {}", path.display(), line, e);
+            }
+
+            let mut converter = Converter::new();
+            for sc in 0..kmap::KEY_COUNT as u8 {
+                let plain = keymap.lookup(sc, false, false, false);
+                let key = Key {
+                    plain: to_symbol(plain),
+                    shift: to_symbol(keymap.lookup(sc, true, false, false)),
+                    altgr: to_symbol(keymap.lookup(sc, false, true, false)),
+                    caps: keymap.lookup(sc, false, false, true) != plain,
+                };
+                converter.keys.insert(sc as u32, key);
+            }
+            let written = converter.write(keymap.name(), "test");
+            let mut again = kmap::Keymap::new();
+            if let Err((line, e)) = kmap::parse(&written, &mut again) {
+                panic!("{} written back, line {}: {}\n{}", path.display(), line, e, written);
+            }
+
+            assert_eq!(keymap.name(), again.name(), "{}", path.display());
+            for sc in 0..kmap::KEY_COUNT as u8 {
+                for modifiers in 0..8 {
+                    let (shift, altgr, caps) = (modifiers & 1 != 0, modifiers & 2 != 0, modifiers & 4 != 0);
+                    assert_eq!(
+                        keymap.lookup(sc, shift, altgr, caps),
+                        again.lookup(sc, shift, altgr, caps),
+                        "{} scancode {:#04x}",
+                        path.display(),
+                        sc
+                    );
+                }
+            }
+            count += 1;
+        }
+        assert_eq!(count, 6);
+    }
+}